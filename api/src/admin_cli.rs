@@ -0,0 +1,96 @@
+use diesel::{Connection, PgConnection};
+
+use common::{models::api_key::ApiScope, services::api_keys};
+use uuid::Uuid;
+
+/// Runs admin command from command line arguments and returns exit code,
+/// none when there's no admin command
+pub fn run() -> Option<i32> {
+    let command = if let Some(name) = arg_value("--create-api-key") {
+        Command::Create(name)
+    } else if let Some(uuid) = arg_value("--revoke-api-key") {
+        Command::Revoke(uuid)
+    } else if std::env::args().any(|arg| arg == "--list-api-keys") {
+        Command::List
+    } else {
+        return None;
+    };
+
+    let db_conn = match PgConnection::establish(&common::construct_db_url_blocking()) {
+        Ok(db_conn) => db_conn,
+        Err(err) => {
+            println!("Couldn't connect to database: {err}. Run with --check-config for details");
+            return Some(1);
+        }
+    };
+
+    Some(match run_command(&db_conn, command) {
+        Ok(()) => 0,
+        Err(err) => {
+            println!("{err}");
+            1
+        }
+    })
+}
+
+enum Command {
+    Create(String),
+    Revoke(String),
+    List,
+}
+
+fn run_command(db_conn: &PgConnection, command: Command) -> Result<(), String> {
+    match command {
+        Command::Create(name) => {
+            let scopes = arg_value("--scopes")
+                .ok_or(
+                    "--create-api-key needs --scopes, e.g. --scopes read_public,read_moderation",
+                )?
+                .split(',')
+                .map(|scope| scope.trim().parse())
+                .collect::<Result<Vec<ApiScope>, _>>()?;
+
+            let (api_key, key) =
+                api_keys::create(db_conn, &name, scopes).map_err(|err| format!("{err:?}"))?;
+
+            println!(
+                "Created api key {} ({}), it isn't shown again:",
+                api_key.name, api_key.uuid
+            );
+            println!("{key}");
+        }
+        Command::Revoke(uuid) => {
+            let uuid =
+                Uuid::parse_str(&uuid).map_err(|err| format!("Invalid uuid \"{uuid}\": {err}"))?;
+
+            match api_keys::revoke(db_conn, uuid).map_err(|err| format!("{err:?}"))? {
+                true => println!("Revoked api key {uuid}"),
+                false => return Err(format!("There's no api key {uuid} that isn't revoked")),
+            }
+        }
+        Command::List => {
+            for api_key in api_keys::get_all(db_conn).map_err(|err| format!("{err:?}"))? {
+                let scopes: Vec<&str> = api_key.scopes.iter().map(ApiScope::as_str).collect();
+                let revoked = match api_key.revoked_at {
+                    Some(revoked_at) => format!(", revoked at {revoked_at}"),
+                    None => String::new(),
+                };
+
+                println!(
+                    "{} {} [{}] created at {}{revoked}",
+                    api_key.uuid,
+                    api_key.name,
+                    scopes.join(", "),
+                    api_key.created_at
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Value that follows `name` in command line arguments, e.g. `--scopes read_public`
+fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
+}
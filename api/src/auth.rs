@@ -0,0 +1,164 @@
+use std::{marker::PhantomData, time::Duration};
+
+use common::{
+    models::api_key::{ApiKey, ApiScope},
+    services::api_keys,
+};
+use rocket::{
+    http::Status,
+    outcome::Outcome,
+    request::{self, FromRequest, Request},
+};
+
+use crate::{db::Db, error::ApiError, rate_limit::RateLimiter};
+
+/// Scope route needs, used as type parameter of `Authorized`
+pub trait Scope {
+    const SCOPE: ApiScope;
+}
+
+pub struct ReadPublic;
+pub struct ReadModeration;
+pub struct Admin;
+
+impl Scope for ReadPublic {
+    const SCOPE: ApiScope = ApiScope::ReadPublic;
+}
+
+impl Scope for ReadModeration {
+    const SCOPE: ApiScope = ApiScope::ReadModeration;
+}
+
+impl Scope for Admin {
+    const SCOPE: ApiScope = ApiScope::Admin;
+}
+
+#[derive(Debug, Clone)]
+pub enum AuthError {
+    /// request has no api key
+    Missing,
+    /// api key doesn't exist or was revoked
+    Invalid,
+    /// api key doesn't have scope route needs
+    MissingScope(ApiScope),
+    /// client sent more than `api.rate_limit_per_minute` requests
+    RateLimited {
+        retry_after: Duration,
+    },
+    Database,
+}
+
+/// Request guard letting through only requests with api key that has scope `S`, sent either as
+/// `Authorization: Bearer <key>` or `X-Api-Key: <key>`. Public data can be read without key
+/// unless `api.public_read` is off. Requests are rate limited per api key, or per ip when
+/// they're sent without valid key.
+pub struct Authorized<S: Scope> {
+    /// none when public data is read without key
+    pub api_key: Option<ApiKey>,
+    scope: PhantomData<S>,
+}
+
+#[rocket::async_trait]
+impl<'r, S: Scope> FromRequest<'r> for Authorized<S> {
+    type Error = AuthError;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        match authorize(request, S::SCOPE).await {
+            Ok(api_key) => Outcome::Success(Authorized {
+                api_key,
+                scope: PhantomData,
+            }),
+            Err((status, err)) => {
+                // catchers don't get errors of guards, they read it from here
+                request.local_cache(|| Some(err.clone()));
+                Outcome::Error((status, err))
+            }
+        }
+    }
+}
+
+async fn authorize(
+    request: &Request<'_>,
+    scope: ApiScope,
+) -> Result<Option<ApiKey>, (Status, AuthError)> {
+    let Some(key) = key(request).map(str::to_owned) else {
+        if scope == ApiScope::ReadPublic && get_config_async!().await.api.public_read {
+            rate_limit(request, &client_ip(request).await)?;
+
+            return Ok(None);
+        }
+
+        return Err((Status::Unauthorized, AuthError::Missing));
+    };
+
+    let db = match request.guard::<Db>().await {
+        Outcome::Success(db) => db,
+        _ => return Err((Status::ServiceUnavailable, AuthError::Database)),
+    };
+
+    match db
+        .run(move |db_conn| Ok(api_keys::get_by_key(db_conn, &key)?))
+        .await
+    {
+        Ok(Some(api_key)) => {
+            rate_limit(request, &format!("key:{}", api_key.uuid))?;
+
+            if api_key.allows(scope) {
+                Ok(Some(api_key))
+            } else {
+                Err((Status::Forbidden, AuthError::MissingScope(scope)))
+            }
+        }
+        // guessing keys counts against ip
+        Ok(None) => {
+            rate_limit(request, &client_ip(request).await)?;
+
+            Err((Status::Unauthorized, AuthError::Invalid))
+        }
+        Err(ApiError::DatabaseUnavailable(report) | ApiError::Timeout(report)) => {
+            error!("{report:?}");
+            Err((Status::ServiceUnavailable, AuthError::Database))
+        }
+        Err(err) => {
+            error!("{err:?}");
+            Err((Status::InternalServerError, AuthError::Database))
+        }
+    }
+}
+
+fn key<'a>(request: &'a Request<'_>) -> Option<&'a str> {
+    let headers = request.headers();
+
+    headers.get_one("X-Api-Key").or_else(|| {
+        headers
+            .get_one("Authorization")
+            .and_then(|authorization| authorization.strip_prefix("Bearer "))
+    })
+}
+
+async fn client_ip(request: &Request<'_>) -> String {
+    let ip = if get_config_async!().await.api.trust_x_real_ip {
+        request.client_ip()
+    } else {
+        request.remote().map(|remote| remote.ip())
+    };
+
+    match ip {
+        Some(ip) => format!("ip:{ip}"),
+        None => "ip:unknown".to_owned(),
+    }
+}
+
+fn rate_limit(request: &Request<'_>, client: &str) -> Result<(), (Status, AuthError)> {
+    let Some(rate_limiter) = request.rocket().state::<RateLimiter>() else {
+        return Ok(());
+    };
+
+    rate_limiter.check(client).map_err(|retry_after| {
+        debug!("rate limited {client}, next request allowed in {retry_after:?}");
+        (
+            Status::TooManyRequests,
+            AuthError::RateLimited { retry_after },
+        )
+    })
+}
@@ -0,0 +1,99 @@
+use std::fmt::Display;
+
+use diesel::{
+    r2d2::{ConnectionManager, Pool},
+    PgConnection,
+};
+use error_stack::{IntoReport, Report, ResultExt};
+use rocket::{
+    http::Status,
+    outcome::Outcome,
+    request::{self, FromRequest, Request},
+};
+
+use crate::error::ApiError;
+
+pub type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+#[derive(Debug)]
+pub enum DbError {
+    /// no connection got free in time or database can't be reached
+    Pool,
+    Query,
+    /// query panicked on its blocking thread
+    Panicked,
+}
+
+impl Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbError::Pool => write!(f, "Couldn't get connection to database from pool"),
+            DbError::Query => write!(f, "Database error"),
+            DbError::Panicked => write!(f, "Database query panicked"),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+/// Pool of `api.db_pool_size` connections whose queries are cancelled after
+/// `api.statement_timeout_ms`
+pub fn pool() -> error_stack::Result<DbPool, DbError> {
+    let mut db_url = common::construct_db_url_blocking();
+    let config = get_config_blocking!();
+
+    if config.api.statement_timeout_ms > 0 {
+        // libpq passes `options` to the server as if they were set with `SET` on every connection
+        let separator = if db_url.contains('?') { '&' } else { '?' };
+        db_url.push_str(&format!(
+            "{separator}options=-c%20statement_timeout%3D{}",
+            config.api.statement_timeout_ms
+        ));
+    }
+
+    Pool::builder()
+        .max_size(config.api.db_pool_size)
+        .build(ConnectionManager::new(db_url))
+        .into_report()
+        .change_context(DbError::Pool)
+}
+
+/// Request guard for running `common::services` queries. They're synchronous diesel calls, so
+/// they run on tokio's blocking threads and async workers stay free for other requests.
+pub struct Db(DbPool);
+
+impl Db {
+    /// Runs `query` with connection from pool, waits for free connection at most r2d2's
+    /// connection timeout (30s) and answers 503 after it
+    pub async fn run<T, F>(&self, query: F) -> Result<T, ApiError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&PgConnection) -> Result<T, ApiError> + Send + 'static,
+    {
+        let pool = self.0.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let db_conn = pool
+                .get()
+                .into_report()
+                .change_context(DbError::Pool)
+                .map_err(ApiError::DatabaseUnavailable)?;
+
+            query(&db_conn)
+        })
+        .await
+        .map_err(|err| ApiError::Database(Report::new(err).change_context(DbError::Panicked)))?
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Db {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        match request.rocket().state::<DbPool>() {
+            Some(pool) => Outcome::Success(Db(pool.clone())),
+            None => Outcome::Error((Status::InternalServerError, ())),
+        }
+    }
+}
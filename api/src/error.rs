@@ -0,0 +1,278 @@
+use error_stack::Report;
+use rocket::{
+    http::Status,
+    request::Request,
+    response::{self, status, Responder},
+    serde::json::Json,
+};
+use serde_derive::Serialize;
+use utoipa::ToSchema;
+
+use crate::{auth::AuthError, db::DbError};
+
+/// Body of every error response, `code` doesn't change between versions so clients can match it
+#[derive(Serialize, ToSchema, Debug)]
+pub struct ErrorBody {
+    pub code: &'static str,
+    pub message: String,
+    /// seconds until next request is allowed, only when rate limited
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+}
+
+impl ErrorBody {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            retry_after_secs: None,
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ErrorBody {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let retry_after_secs = self.retry_after_secs;
+        let mut response = Json(self).respond_to(request)?;
+
+        if let Some(retry_after_secs) = retry_after_secs {
+            response.set_raw_header("Retry-After", retry_after_secs.to_string());
+        }
+
+        Ok(response)
+    }
+}
+
+/// Error of api route, answered with json `ErrorBody`
+#[derive(Debug)]
+pub enum ApiError {
+    ChannelNotTracked(String),
+    StreamNotFound(i32),
+    UserNotFound(String),
+    /// invalid query parameters
+    BadRequest(String),
+    /// request over `api.max_page_size` or `api.max_time_range_hours`
+    LimitExceeded(String),
+    /// no free connection in pool or connection to database was lost
+    DatabaseUnavailable(Report<DbError>),
+    /// query ran over `api.statement_timeout_ms`
+    Timeout(Report<DbError>),
+    Database(Report<DbError>),
+}
+
+impl ApiError {
+    fn status(&self) -> Status {
+        match self {
+            ApiError::ChannelNotTracked(_)
+            | ApiError::StreamNotFound(_)
+            | ApiError::UserNotFound(_) => Status::NotFound,
+            ApiError::BadRequest(_) => Status::BadRequest,
+            ApiError::LimitExceeded(_) => Status::PayloadTooLarge,
+            ApiError::DatabaseUnavailable(_) | ApiError::Timeout(_) => Status::ServiceUnavailable,
+            ApiError::Database(_) => Status::InternalServerError,
+        }
+    }
+
+    fn body(&self) -> ErrorBody {
+        match self {
+            ApiError::ChannelNotTracked(channel_name) => ErrorBody::new(
+                "channel_not_tracked",
+                format!("channel {channel_name} isn't tracked"),
+            ),
+            ApiError::StreamNotFound(stream_session_id) => ErrorBody::new(
+                "stream_not_found",
+                format!("channel has no stream with id {stream_session_id}"),
+            ),
+            ApiError::UserNotFound(twitch_user_id) => ErrorBody::new(
+                "user_not_found",
+                format!("user with twitch id {twitch_user_id} wasn't seen in any channel"),
+            ),
+            ApiError::BadRequest(reason) => ErrorBody::new("invalid_parameter", reason.clone()),
+            ApiError::LimitExceeded(reason) => ErrorBody::new("limit_exceeded", reason.clone()),
+            ApiError::DatabaseUnavailable(_) => database_unavailable(),
+            ApiError::Timeout(_) => ErrorBody::new(
+                "query_timeout",
+                "query took too long, try smaller time range or page",
+            ),
+            ApiError::Database(_) => ErrorBody::new("database_error", "database error"),
+        }
+    }
+}
+
+impl From<Report<diesel::result::Error>> for ApiError {
+    fn from(report: Report<diesel::result::Error>) -> Self {
+        use diesel::result::{DatabaseErrorKind, Error};
+
+        let variant = match report.current_context() {
+            Error::DatabaseError(_, info) if info.message().contains("statement timeout") => {
+                ApiError::Timeout
+            }
+            Error::DatabaseError(DatabaseErrorKind::UnableToSendCommand, _) => {
+                ApiError::DatabaseUnavailable
+            }
+            Error::DatabaseError(_, info) if is_connection_lost(info.message()) => {
+                ApiError::DatabaseUnavailable
+            }
+            _ => ApiError::Database,
+        };
+
+        variant(report.change_context(DbError::Query))
+    }
+}
+
+/// Connection broke while query was running, e.g. database restarted. Diesel reports these as
+/// unknown errors with message from libpq or server, so they're told apart by the message.
+fn is_connection_lost(message: &str) -> bool {
+    const MESSAGES: [&str; 6] = [
+        "server closed the connection unexpectedly",
+        "terminating connection",
+        "no connection to the server",
+        "connection to server was lost",
+        "could not receive data from server",
+        "SSL connection has been closed unexpectedly",
+    ];
+
+    MESSAGES.iter().any(|lost| message.contains(lost))
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        match &self {
+            ApiError::Database(report) | ApiError::DatabaseUnavailable(report) => {
+                error!("{report:?}")
+            }
+            ApiError::Timeout(report) => warn!("{report:?}"),
+            _ => {}
+        }
+
+        status::Custom(self.status(), self.body()).respond_to(request)
+    }
+}
+
+fn database_unavailable() -> ErrorBody {
+    ErrorBody::new(
+        "database_unavailable",
+        "database is unavailable, try again later",
+    )
+}
+
+/// Body for failure of `Authorized` guard, which leaves its error in request's local cache
+fn auth_error(request: &Request, fallback: ErrorBody) -> ErrorBody {
+    match request.local_cache(|| None::<AuthError>) {
+        Some(AuthError::Missing) => ErrorBody::new(
+            "api_key_missing",
+            "api key has to be sent as `Authorization: Bearer <key>` or `X-Api-Key: <key>`",
+        ),
+        Some(AuthError::Invalid) => {
+            ErrorBody::new("api_key_invalid", "api key doesn't exist or was revoked")
+        }
+        Some(AuthError::MissingScope(scope)) => ErrorBody::new(
+            "scope_missing",
+            format!("api key doesn't have scope {scope}"),
+        ),
+        Some(AuthError::RateLimited { retry_after }) => ErrorBody {
+            retry_after_secs: Some(retry_after.as_secs() + 1),
+            ..ErrorBody::new("rate_limited", "too many requests")
+        },
+        Some(AuthError::Database) | None => fallback,
+    }
+}
+
+#[catch(400)]
+pub fn bad_request() -> ErrorBody {
+    ErrorBody::new("bad_request", "request is malformed")
+}
+
+#[catch(401)]
+pub fn unauthorized(request: &Request) -> ErrorBody {
+    auth_error(request, ErrorBody::new("unauthorized", "api key is needed"))
+}
+
+#[catch(403)]
+pub fn forbidden(request: &Request) -> ErrorBody {
+    auth_error(
+        request,
+        ErrorBody::new("forbidden", "api key isn't allowed"),
+    )
+}
+
+#[catch(404)]
+pub fn not_found(request: &Request) -> ErrorBody {
+    ErrorBody::new(
+        "not_found",
+        format!("no route for {} {}", request.method(), request.uri()),
+    )
+}
+
+#[catch(422)]
+pub fn unprocessable_entity() -> ErrorBody {
+    ErrorBody::new("invalid_body", "request body isn't valid for this route")
+}
+
+#[catch(429)]
+pub fn too_many_requests(request: &Request) -> ErrorBody {
+    auth_error(request, ErrorBody::new("rate_limited", "too many requests"))
+}
+
+#[catch(500)]
+pub fn internal_server_error() -> ErrorBody {
+    ErrorBody::new("internal_error", "internal error")
+}
+
+#[catch(503)]
+pub fn service_unavailable() -> ErrorBody {
+    database_unavailable()
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::result::{DatabaseErrorKind, Error};
+
+    use super::*;
+
+    fn from_database_error(kind: DatabaseErrorKind, message: &str) -> ApiError {
+        Report::new(Error::DatabaseError(kind, Box::new(message.to_owned()))).into()
+    }
+
+    #[test]
+    fn lost_connection_is_unavailable() {
+        let messages = [
+            "server closed the connection unexpectedly\n\tThis probably means the server \
+             terminated abnormally\n\tbefore or while processing the request.\n",
+            "terminating connection due to administrator command",
+            "no connection to the server\n",
+        ];
+
+        for message in messages {
+            let err = from_database_error(DatabaseErrorKind::__Unknown, message);
+            assert!(matches!(err, ApiError::DatabaseUnavailable(_)), "{message}");
+        }
+    }
+
+    #[test]
+    fn unable_to_send_command_is_unavailable() {
+        let err = from_database_error(DatabaseErrorKind::UnableToSendCommand, "");
+
+        assert!(matches!(err, ApiError::DatabaseUnavailable(_)));
+    }
+
+    #[test]
+    fn statement_timeout_is_timeout() {
+        let err = from_database_error(
+            DatabaseErrorKind::__Unknown,
+            "canceling statement due to statement timeout",
+        );
+
+        assert!(matches!(err, ApiError::Timeout(_)));
+    }
+
+    #[test]
+    fn other_errors_are_database_errors() {
+        let err = from_database_error(
+            DatabaseErrorKind::UniqueViolation,
+            "duplicate key value violates unique constraint",
+        );
+
+        assert!(matches!(err, ApiError::Database(_)));
+    }
+}
@@ -1,8 +1,9 @@
-#![feature(proc_macro_hygiene, decl_macro)]
+use std::process::exit;
 
-use rocket_contrib::database;
+use rocket::figment::Figment;
 
-use common::config;
+use common::{config, config_check};
+use rate_limit::RateLimiter;
 
 #[macro_use]
 extern crate rocket;
@@ -10,27 +11,119 @@ extern crate rocket;
 #[macro_use]
 extern crate log;
 
+#[macro_use]
+extern crate common;
+
 // #[macro_use]
 // mod config;
 // mod models;
 // mod schema;
 // mod twitch_watcher;
 
-#[database("chat")]
-struct ChatDbConn(diesel::PgConnection);
+mod admin_cli;
+mod auth;
+mod db;
+mod error;
+mod rate_limit;
+mod routes;
 
 fn main() -> anyhow::Result<()> {
     let _ = dotenvy::dotenv();
 
-    config::load_blocking()?;
+    if let Err(err) = config::load_blocking() {
+        println!("{:?}", err);
+        exit(1);
+    }
+
+    if std::env::args().any(|arg| arg == "--check-config") {
+        match config_check::check_connectivity_blocking() {
+            Ok(()) => {
+                println!("Config is valid, database and twitch api are reachable");
+                exit(0);
+            }
+            Err(err) => {
+                println!("{:?}", err);
+                exit(1);
+            }
+        }
+    }
+
+    if let Some(exit_code) = admin_cli::run() {
+        exit(exit_code);
+    }
 
-    // TODO pass info from my config to rocket config
+    let rocket_config = match rocket_config() {
+        Ok(rocket_config) => rocket_config,
+        Err(err) => {
+            println!("Invalid api config: {}", err);
+            exit(1);
+        }
+    };
 
-    rocket::ignite()
-        .attach(ChatDbConn::fairing())
-        .mount("/", routes![])
-        .launch();
+    let db_pool = match db::pool() {
+        Ok(db_pool) => db_pool,
+        Err(err) => {
+            println!("{:?}", err);
+            exit(1);
+        }
+    };
+
+    let rate_limiter = RateLimiter::new(get_config_blocking!().api.rate_limit_per_minute);
+
+    let rocket = rocket::custom(rocket_config)
+        .manage(db_pool)
+        .manage(rate_limiter)
+        .register(
+            "/",
+            catchers![
+                error::bad_request,
+                error::unauthorized,
+                error::forbidden,
+                error::not_found,
+                error::unprocessable_entity,
+                error::too_many_requests,
+                error::internal_server_error,
+                error::service_unavailable,
+            ],
+        )
+        .mount("/", routes::all());
+
+    if let Err(err) = rocket::execute(rocket.launch()) {
+        println!("{}", err);
+        exit(1);
+    }
 
     trace!("exiting");
     Ok(())
 }
+
+/// Builds rocket config from our config, so `TCH_` env overrides work for api too.
+/// Rocket.toml and `ROCKET_` env variables aren't used.
+fn rocket_config() -> Result<rocket::Config, Box<rocket::figment::Error>> {
+    let config = get_config_blocking!();
+
+    let mut figment = Figment::from(rocket::Config::default())
+        .merge(("address", config.api.address))
+        .merge(("port", config.api.port));
+
+    if let Some(workers) = config.api.workers {
+        figment = figment.merge(("workers", workers));
+    }
+
+    if let Some(tls) = &config.api.tls {
+        figment = figment
+            .merge(("tls.certs", &tls.certs))
+            .merge(("tls.key", &tls.key));
+    }
+
+    let rocket_config: rocket::Config = figment.extract().map_err(Box::new)?;
+
+    if config.api.tls.is_some() && !rocket_config.tls_enabled() {
+        // rocket sets up logging only once it's created, so this can't go through log
+        println!(
+            "Warning: api.tls is set, but api was built without rocket's tls feature, serving http"
+        );
+    }
+
+    Ok(rocket_config)
+}
@@ -0,0 +1,179 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// buckets of clients are forgotten once there's this many of them and they're full again
+const MAX_IDLE_CLIENTS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Token bucket per client (api key or ip), holding `per_minute` requests and refilled
+/// continuously, so bursts up to the whole minute are allowed. Kept in memory of single api
+/// instance, managed as rocket state.
+pub struct RateLimiter {
+    per_minute: u32,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes one request from bucket of `client`, `Err` holds time until next one is allowed
+    pub fn check(&self, client: &str) -> Result<(), Duration> {
+        self.check_at(client, Instant::now())
+    }
+
+    fn check_at(&self, client: &str, now: Instant) -> Result<(), Duration> {
+        if self.per_minute == 0 {
+            return Ok(());
+        }
+
+        let capacity = self.per_minute as f64;
+        let per_sec = capacity / 60.0;
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= MAX_IDLE_CLIENTS && !buckets.contains_key(client) {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated_at).as_secs_f64() * per_sec
+                    < capacity
+            });
+        }
+
+        let bucket = buckets.entry(client.to_owned()).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+        let refilled = now.duration_since(bucket.updated_at).as_secs_f64() * per_sec;
+        bucket.tokens = (bucket.tokens + refilled).min(capacity);
+        bucket.updated_at = now;
+
+        if bucket.tokens < 1.0 {
+            return Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_sec));
+        }
+        bucket.tokens -= 1.0;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_burst_of_whole_minute() {
+        let limiter = RateLimiter::new(3);
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(limiter.check_at("client", now), Ok(()));
+        }
+        assert!(limiter.check_at("client", now).is_err());
+    }
+
+    #[test]
+    fn retry_after_is_time_until_next_token() {
+        let limiter = RateLimiter::new(60);
+        let now = Instant::now();
+
+        for _ in 0..60 {
+            limiter.check_at("client", now).unwrap();
+        }
+
+        assert_eq!(
+            limiter.check_at("client", now + Duration::from_millis(250)),
+            Err(Duration::from_millis(750))
+        );
+    }
+
+    #[test]
+    fn refills_continuously_up_to_capacity() {
+        let limiter = RateLimiter::new(2);
+        let now = Instant::now();
+
+        limiter.check_at("client", now).unwrap();
+        limiter.check_at("client", now).unwrap();
+
+        // one token per 30 seconds
+        assert!(limiter
+            .check_at("client", now + Duration::from_secs(29))
+            .is_err());
+        assert_eq!(
+            limiter.check_at("client", now + Duration::from_secs(30)),
+            Ok(())
+        );
+
+        // long idle time doesn't add more than a minute worth of tokens
+        let later = now + Duration::from_secs(3600);
+        assert_eq!(limiter.check_at("client", later), Ok(()));
+        assert_eq!(limiter.check_at("client", later), Ok(()));
+        assert!(limiter.check_at("client", later).is_err());
+    }
+
+    #[test]
+    fn clients_have_separate_buckets() {
+        let limiter = RateLimiter::new(1);
+        let now = Instant::now();
+
+        assert_eq!(limiter.check_at("first", now), Ok(()));
+        assert!(limiter.check_at("first", now).is_err());
+        assert_eq!(limiter.check_at("second", now), Ok(()));
+    }
+
+    #[test]
+    fn zero_per_minute_disables_limit() {
+        let limiter = RateLimiter::new(0);
+        let now = Instant::now();
+
+        for _ in 0..100 {
+            assert_eq!(limiter.check_at("client", now), Ok(()));
+        }
+    }
+
+    #[test]
+    fn evicts_full_buckets_when_there_are_too_many() {
+        let limiter = RateLimiter::new(60);
+        let now = Instant::now();
+
+        limiter.check_at("busy", now).unwrap();
+        for client in 0..MAX_IDLE_CLIENTS - 1 {
+            limiter.check_at(&client.to_string(), now).unwrap();
+        }
+
+        // the others are full again after a second, busy one used whole minute
+        for _ in 0..59 {
+            limiter.check_at("busy", now).unwrap();
+        }
+        limiter
+            .check_at("new", now + Duration::from_secs(1))
+            .unwrap();
+
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.len(), 2);
+        assert!(buckets.contains_key("busy"));
+        assert!(buckets.contains_key("new"));
+    }
+
+    #[test]
+    fn keeps_buckets_while_they_are_not_full() {
+        let limiter = RateLimiter::new(60);
+        let now = Instant::now();
+
+        for client in 0..MAX_IDLE_CLIENTS {
+            limiter.check_at(&client.to_string(), now).unwrap();
+        }
+        limiter.check_at("new", now).unwrap();
+
+        assert_eq!(limiter.buckets.lock().unwrap().len(), MAX_IDLE_CLIENTS + 1);
+    }
+}
@@ -0,0 +1,106 @@
+use common::{
+    dto,
+    models::user_erasure::ErasureMode,
+    services::{user_erasures, users},
+};
+use rocket::serde::json::Json;
+use serde_derive::Deserialize;
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{Admin, Authorized},
+    db::Db,
+    error::ApiError,
+};
+
+use super::ApiResult;
+
+#[derive(Deserialize, ToSchema)]
+pub struct ErasureRequest {
+    pub mode: ErasureMode,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Erases user from every channel, same as `twitch_collector --erase-user`
+#[utoipa::path(
+    post,
+    path = "/admin/users/{twitch_user_id}/erasure",
+    tag = "admin",
+    security(("api_key" = []), ("bearer" = [])),
+    params(
+        ("twitch_user_id" = String, Path, description = "twitch id of user"),
+    ),
+    request_body = ErasureRequest,
+    responses((status = 200, description = "Audit record of the erasure", body = dto::UserErasure)),
+)]
+#[post(
+    "/admin/users/<twitch_user_id>/erasure",
+    format = "json",
+    data = "<request>"
+)]
+pub async fn erase_user(
+    auth: Authorized<Admin>,
+    db: Db,
+    twitch_user_id: String,
+    request: Json<ErasureRequest>,
+) -> ApiResult<dto::UserErasure> {
+    let requested_by = match &auth.api_key {
+        Some(api_key) => format!("api key {} ({})", api_key.name, api_key.uuid),
+        None => "api".to_owned(),
+    };
+
+    let request = request.into_inner();
+
+    let erasure = db
+        .run(move |db_conn| {
+            match users::erase_user(
+                db_conn,
+                &twitch_user_id,
+                request.mode,
+                &requested_by,
+                request.reason.as_deref(),
+            )? {
+                Some(erasure) => Ok(erasure),
+                None => Err(ApiError::UserNotFound(twitch_user_id)),
+            }
+        })
+        .await?;
+
+    Ok(Json(erasure.into()))
+}
+
+/// Audit log of erasures of the user
+#[utoipa::path(
+    get,
+    path = "/admin/users/{twitch_user_id}/erasures",
+    tag = "admin",
+    security(("api_key" = []), ("bearer" = [])),
+    params(
+        ("twitch_user_id" = String, Path, description = "twitch id of user"),
+    ),
+    responses((
+        status = 200,
+        description = "Audit log of erasures of user",
+        body = [dto::UserErasure]
+    )),
+)]
+#[get("/admin/users/<twitch_user_id>/erasures")]
+pub async fn get_erasures(
+    _auth: Authorized<Admin>,
+    db: Db,
+    twitch_user_id: String,
+) -> ApiResult<Vec<dto::UserErasure>> {
+    let erasures = db
+        .run(move |db_conn| {
+            Ok(user_erasures::get_by_twitch_user_id(
+                db_conn,
+                &twitch_user_id,
+            )?)
+        })
+        .await?;
+
+    Ok(Json(
+        erasures.into_iter().map(dto::UserErasure::from).collect(),
+    ))
+}
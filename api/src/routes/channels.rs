@@ -0,0 +1,283 @@
+use chrono::Duration;
+use common::{
+    dto,
+    models::channel::Channel,
+    services::{
+        channels::{self, get_channel_by_name},
+        collection_gaps, messages, room_state_changes, stream_sessions,
+    },
+};
+use diesel::PgConnection;
+use rocket::serde::json::Json;
+
+use crate::{
+    auth::{Authorized, ReadModeration, ReadPublic},
+    db::Db,
+    error::ApiError,
+};
+
+use super::{page_size, ApiResult, Time};
+
+#[utoipa::path(
+    get,
+    path = "/channels",
+    tag = "channels",
+    security(("api_key" = []), ("bearer" = []), ()),
+    responses((status = 200, description = "Tracked channels", body = [dto::Channel])),
+)]
+#[get("/channels")]
+pub async fn get_channels(_auth: Authorized<ReadPublic>, db: Db) -> ApiResult<Vec<dto::Channel>> {
+    let channels = db
+        .run(|db_conn| Ok(channels::get_channels(db_conn)?))
+        .await?;
+
+    Ok(Json(channels.into_iter().map(dto::Channel::from).collect()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/channels/{channel_name}",
+    tag = "channels",
+    security(("api_key" = []), ("bearer" = []), ()),
+    params(
+        ("channel_name" = String, Path, description = "current name of channel"),
+    ),
+    responses((status = 200, description = "Channel", body = dto::Channel)),
+)]
+#[get("/channels/<channel_name>")]
+pub async fn get_channel(
+    _auth: Authorized<ReadPublic>,
+    db: Db,
+    channel_name: String,
+) -> ApiResult<dto::Channel> {
+    let channel = db
+        .run(move |db_conn| tracked_channel(db_conn, channel_name))
+        .await?;
+
+    Ok(Json(channel.into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/channels/{channel_name}/room_states",
+    tag = "channels",
+    security(("api_key" = []), ("bearer" = [])),
+    params(
+        ("channel_name" = String, Path, description = "current name of channel"),
+    ),
+    responses((status = 200, description = "Changes of chat modes", body = [dto::RoomStateChange])),
+)]
+#[get("/channels/<channel_name>/room_states")]
+pub async fn get_room_state_changes(
+    _auth: Authorized<ReadModeration>,
+    db: Db,
+    channel_name: String,
+) -> ApiResult<Vec<dto::RoomStateChange>> {
+    let room_state_changes = db
+        .run(move |db_conn| {
+            let channel = tracked_channel(db_conn, channel_name)?;
+
+            Ok(room_state_changes::get_by_channel_id(db_conn, channel.id)?)
+        })
+        .await?;
+
+    Ok(Json(
+        room_state_changes
+            .into_iter()
+            .map(dto::RoomStateChange::from)
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/channels/{channel_name}/streams",
+    tag = "channels",
+    security(("api_key" = []), ("bearer" = []), ()),
+    params(
+        ("channel_name" = String, Path, description = "current name of channel"),
+    ),
+    responses((status = 200, description = "Streams, the most recent first", body = [dto::StreamSession])),
+)]
+#[get("/channels/<channel_name>/streams")]
+pub async fn get_streams(
+    _auth: Authorized<ReadPublic>,
+    db: Db,
+    channel_name: String,
+) -> ApiResult<Vec<dto::StreamSession>> {
+    let stream_sessions = db
+        .run(move |db_conn| {
+            let channel = tracked_channel(db_conn, channel_name)?;
+
+            Ok(stream_sessions::get_by_channel_id(db_conn, channel.id)?)
+        })
+        .await?;
+
+    Ok(Json(
+        stream_sessions
+            .into_iter()
+            .map(dto::StreamSession::from)
+            .collect(),
+    ))
+}
+
+/// Page of messages of stream, next page starts after id of the last message
+#[utoipa::path(
+    get,
+    path = "/channels/{channel_name}/streams/{stream_session_id}/messages",
+    tag = "channels",
+    security(("api_key" = []), ("bearer" = []), ()),
+    params(
+        ("channel_name" = String, Path, description = "current name of channel"),
+        ("stream_session_id" = i32, Path, description = "id of stream"),
+        ("after" = Option<i64>, Query, description = "id of last message of previous page"),
+        (
+            "limit" = Option<i64>,
+            Query,
+            minimum = 1,
+            description = "page size, at most `api.max_page_size` which is also the default"
+        ),
+    ),
+    responses((status = 200, description = "Page of messages of stream", body = [dto::Message])),
+)]
+#[get("/channels/<channel_name>/streams/<stream_session_id>/messages?<after>&<limit>")]
+pub async fn get_stream_messages(
+    _auth: Authorized<ReadPublic>,
+    db: Db,
+    channel_name: String,
+    stream_session_id: i32,
+    after: Option<i64>,
+    limit: Option<i64>,
+) -> ApiResult<Vec<dto::Message>> {
+    let limit = page_size(limit).await?;
+
+    let messages = db
+        .run(move |db_conn| {
+            let channel = tracked_channel(db_conn, channel_name)?;
+
+            // stream has to belong to the channel from the path
+            let stream_session = match stream_sessions::get_by_id(db_conn, stream_session_id)? {
+                Some(stream_session) if stream_session.channel_id == channel.id => stream_session,
+                _ => return Err(ApiError::StreamNotFound(stream_session_id)),
+            };
+
+            Ok(messages::get_by_stream_session(
+                db_conn,
+                &stream_session,
+                after.unwrap_or(0),
+                limit,
+            )?)
+        })
+        .await?;
+
+    Ok(Json(messages.into_iter().map(dto::Message::from).collect()))
+}
+
+/// Page of messages of channel sent from `from` until `to`, which can be at most
+/// `api.max_time_range_hours` apart, next page starts after id of the last message
+#[utoipa::path(
+    get,
+    path = "/channels/{channel_name}/messages",
+    tag = "channels",
+    security(("api_key" = []), ("bearer" = []), ()),
+    params(
+        ("channel_name" = String, Path, description = "current name of channel"),
+        ("from" = DateTime<Utc>, Query, description = "start of time range, inclusive"),
+        (
+            "to" = DateTime<Utc>,
+            Query,
+            description = "end of time range, exclusive, at most `api.max_time_range_hours` after from"
+        ),
+        ("after" = Option<i64>, Query, description = "id of last message of previous page"),
+        (
+            "limit" = Option<i64>,
+            Query,
+            minimum = 1,
+            description = "page size, at most `api.max_page_size` which is also the default"
+        ),
+    ),
+    responses((status = 200, description = "Page of messages of channel sent in time range", body = [dto::Message])),
+)]
+#[get("/channels/<channel_name>/messages?<from>&<to>&<after>&<limit>")]
+pub async fn get_messages(
+    _auth: Authorized<ReadPublic>,
+    db: Db,
+    channel_name: String,
+    from: Option<Time>,
+    to: Option<Time>,
+    after: Option<i64>,
+    limit: Option<i64>,
+) -> ApiResult<Vec<dto::Message>> {
+    let limit = page_size(limit).await?;
+
+    let (from, to) = match (from, to) {
+        (Some(Time(from)), Some(Time(to))) if from < to => (from, to),
+        (Some(_), Some(_)) => {
+            return Err(ApiError::BadRequest("from has to be before to".to_owned()))
+        }
+        _ => {
+            return Err(ApiError::BadRequest(
+                "from and to have to be RFC 3339 times, e.g. 2023-01-21T18:00:00Z".to_owned(),
+            ))
+        }
+    };
+
+    let max_time_range_hours = get_config_async!().await.api.max_time_range_hours;
+    if to - from > Duration::hours(max_time_range_hours) {
+        return Err(ApiError::LimitExceeded(format!(
+            "from and to can be at most {max_time_range_hours} hours apart"
+        )));
+    }
+
+    let messages = db
+        .run(move |db_conn| {
+            let channel = tracked_channel(db_conn, channel_name)?;
+
+            Ok(messages::get_by_channel_id(
+                db_conn,
+                channel.id,
+                from,
+                to,
+                after.unwrap_or(0),
+                limit,
+            )?)
+        })
+        .await?;
+
+    Ok(Json(messages.into_iter().map(dto::Message::from).collect()))
+}
+
+/// Time ranges we have data for, so "nothing found" can be told apart from "wasn't recording"
+#[utoipa::path(
+    get,
+    path = "/channels/{channel_name}/coverage",
+    tag = "channels",
+    security(("api_key" = []), ("bearer" = []), ()),
+    params(
+        ("channel_name" = String, Path, description = "current name of channel"),
+    ),
+    responses((status = 200, description = "Time ranges there's data for", body = dto::Coverage)),
+)]
+#[get("/channels/<channel_name>/coverage")]
+pub async fn get_coverage(
+    _auth: Authorized<ReadPublic>,
+    db: Db,
+    channel_name: String,
+) -> ApiResult<dto::Coverage> {
+    let coverage = db
+        .run(move |db_conn| {
+            let channel = tracked_channel(db_conn, channel_name)?;
+
+            Ok(collection_gaps::get_coverage(db_conn, &channel)?)
+        })
+        .await?;
+
+    Ok(Json(coverage.into()))
+}
+
+fn tracked_channel(db_conn: &PgConnection, channel_name: String) -> Result<Channel, ApiError> {
+    match get_channel_by_name(db_conn, &channel_name)? {
+        Some(channel) => Ok(channel),
+        None => Err(ApiError::ChannelNotTracked(channel_name)),
+    }
+}
@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+use rocket::{
+    form::{self, FromFormField, ValueField},
+    serde::json::Json,
+    Route,
+};
+
+use crate::error::ApiError;
+
+pub mod admin;
+pub mod channels;
+pub mod openapi;
+pub mod users;
+
+pub type ApiResult<T> = Result<Json<T>, ApiError>;
+
+/// Every route of the api, each of them except `/openapi.json` has to be in `openapi::ApiDoc`
+pub fn all() -> Vec<Route> {
+    routes![
+        channels::get_channels,
+        channels::get_channel,
+        channels::get_room_state_changes,
+        channels::get_streams,
+        channels::get_stream_messages,
+        channels::get_messages,
+        channels::get_coverage,
+        users::get_user,
+        users::get_old_names,
+        admin::erase_user,
+        admin::get_erasures,
+        openapi::get_openapi,
+    ]
+}
+
+/// RFC 3339 time in query, e.g. `2023-01-21T18:00:00Z`
+pub struct Time(pub DateTime<Utc>);
+
+impl<'v> FromFormField<'v> for Time {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        DateTime::parse_from_rfc3339(field.value)
+            .map(|time| Time(time.with_timezone(&Utc)))
+            .map_err(|_| form::Error::validation("has to be RFC 3339 time").into())
+    }
+}
+
+/// Checks `limit` query parameter, missing one means `api.max_page_size`
+async fn page_size(limit: Option<i64>) -> Result<i64, ApiError> {
+    let max_page_size = get_config_async!().await.api.max_page_size;
+
+    match limit {
+        None => Ok(max_page_size),
+        Some(limit) if limit < 1 => Err(ApiError::BadRequest(
+            "limit has to be greater than 0".to_owned(),
+        )),
+        Some(limit) if limit > max_page_size => Err(ApiError::LimitExceeded(format!(
+            "limit can be at most {max_page_size}"
+        ))),
+        Some(limit) => Ok(limit),
+    }
+}
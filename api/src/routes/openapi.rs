@@ -0,0 +1,228 @@
+// OpenAPI 3 document of the api, derived from `#[utoipa::path]` of routes and `ToSchema` of
+// `common::dto`. Error responses are the same for every route, so they're added by `Modify`.
+
+use common::{dto, models};
+use rocket::serde::json::Json;
+use utoipa::{
+    openapi::{
+        path::{Operation, PathItem},
+        security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+        ContentBuilder, Ref, ResponseBuilder,
+    },
+    Modify, OpenApi,
+};
+
+use crate::error::ErrorBody;
+
+use super::{admin, channels, users};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "twitch chat collector api"),
+    paths(
+        channels::get_channels,
+        channels::get_channel,
+        channels::get_room_state_changes,
+        channels::get_streams,
+        channels::get_stream_messages,
+        channels::get_messages,
+        channels::get_coverage,
+        users::get_user,
+        users::get_old_names,
+        admin::erase_user,
+        admin::get_erasures,
+    ),
+    components(schemas(
+        dto::Channel,
+        dto::User,
+        dto::UserOldName,
+        dto::Resub,
+        dto::Message,
+        dto::StreamSession,
+        dto::RoomStateChange,
+        dto::TimeRange,
+        dto::CollectionGap,
+        dto::Coverage,
+        dto::UserErasure,
+        models::channel::EventKind,
+        models::message::MsgType,
+        models::resub::Tier,
+        models::collection_gap::GapReason,
+        models::user_erasure::ErasureMode,
+        admin::ErasureRequest,
+        ErrorBody,
+    )),
+    modifiers(&SecuritySchemes, &ErrorResponses)
+)]
+pub struct ApiDoc;
+
+#[get("/openapi.json")]
+pub fn get_openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// Api key goes to `X-Api-Key` header or is sent as bearer token, see `auth`
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-Api-Key"))),
+        );
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+/// Every route answers errors with `ErrorBody`, see `ApiError` and catchers
+struct ErrorResponses;
+
+impl Modify for ErrorResponses {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let errors = [
+            (
+                "400",
+                "invalid parameters",
+                &["invalid_parameter", "bad_request"][..],
+            ),
+            (
+                "401",
+                "missing or invalid api key",
+                &["api_key_missing", "api_key_invalid"],
+            ),
+            ("403", "api key doesn't have the scope", &["scope_missing"]),
+            (
+                "404",
+                "not found",
+                &[
+                    "channel_not_tracked",
+                    "stream_not_found",
+                    "user_not_found",
+                    "not_found",
+                ],
+            ),
+            (
+                "413",
+                "over `api.max_page_size` or `api.max_time_range_hours`",
+                &["limit_exceeded"],
+            ),
+            ("422", "request body isn't valid", &["invalid_body"]),
+            (
+                "429",
+                "over `api.rate_limit_per_minute`, Retry-After header is set",
+                &["rate_limited"],
+            ),
+            (
+                "500",
+                "database error",
+                &["database_error", "internal_error"],
+            ),
+            (
+                "503",
+                "query ran over `api.statement_timeout_ms` or database is unavailable",
+                &["query_timeout", "database_unavailable"],
+            ),
+        ];
+
+        for operation in openapi.paths.paths.values_mut().flat_map(operations) {
+            for (status, description, codes) in errors {
+                let response = ResponseBuilder::new()
+                    .description(format!(
+                        "{description}, code is one of: {}",
+                        codes.join(", ")
+                    ))
+                    .content(
+                        "application/json",
+                        ContentBuilder::new()
+                            .schema(Some(Ref::from_schema_name("ErrorBody")))
+                            .build(),
+                    )
+                    .build();
+
+                operation
+                    .responses
+                    .responses
+                    .insert(status.to_owned(), response.into());
+            }
+        }
+    }
+}
+
+fn operations(path_item: &mut PathItem) -> impl Iterator<Item = &mut Operation> {
+    [
+        &mut path_item.get,
+        &mut path_item.put,
+        &mut path_item.post,
+        &mut path_item.delete,
+        &mut path_item.options,
+        &mut path_item.head,
+        &mut path_item.patch,
+        &mut path_item.trace,
+    ]
+    .into_iter()
+    .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `<name>` segments of rocket become `{name}` in OpenAPI, query isn't part of path
+    fn openapi_path(rocket_path: &str) -> String {
+        rocket_path
+            .split('?')
+            .next()
+            .unwrap()
+            .replace('<', "{")
+            .replace('>', "}")
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let document = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        for route in super::super::all() {
+            let path = openapi_path(&route.uri.to_string());
+            if path == "/openapi.json" {
+                continue;
+            }
+
+            let method = route.method.as_str().to_lowercase();
+            assert!(
+                document["paths"][&path][&method].is_object(),
+                "{method} {path} isn't in OpenAPI document"
+            );
+        }
+    }
+
+    #[test]
+    fn every_documented_path_is_route() {
+        let routes: Vec<String> = super::super::all()
+            .iter()
+            .map(|route| openapi_path(&route.uri.to_string()))
+            .collect();
+
+        for path in ApiDoc::openapi().paths.paths.keys() {
+            assert!(routes.contains(path), "{path} isn't mounted");
+        }
+    }
+
+    #[test]
+    fn every_operation_has_error_responses() {
+        for (path, mut path_item) in ApiDoc::openapi().paths.paths {
+            for operation in operations(&mut path_item) {
+                for status in ["200", "401", "404", "429", "503"] {
+                    assert!(
+                        operation.responses.responses.contains_key(status),
+                        "{path} doesn't answer {status}"
+                    );
+                }
+            }
+        }
+    }
+}
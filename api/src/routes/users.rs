@@ -0,0 +1,79 @@
+use common::{
+    dto,
+    models::user::User,
+    services::{users, users_old_names},
+};
+use diesel::PgConnection;
+use rocket::serde::json::Json;
+
+use crate::{
+    auth::{Authorized, ReadModeration, ReadPublic},
+    db::Db,
+    error::ApiError,
+};
+
+use super::ApiResult;
+
+#[utoipa::path(
+    get,
+    path = "/users/{twitch_user_id}",
+    tag = "users",
+    security(("api_key" = []), ("bearer" = []), ()),
+    params(
+        ("twitch_user_id" = String, Path, description = "twitch id of user"),
+    ),
+    responses((status = 200, description = "User", body = dto::User)),
+)]
+#[get("/users/<twitch_user_id>")]
+pub async fn get_user(
+    _auth: Authorized<ReadPublic>,
+    db: Db,
+    twitch_user_id: String,
+) -> ApiResult<dto::User> {
+    let user = db
+        .run(move |db_conn| seen_user(db_conn, twitch_user_id))
+        .await?;
+
+    Ok(Json(user.into()))
+}
+
+/// Names user had before, e.g. to recognize ban evaders
+#[utoipa::path(
+    get,
+    path = "/users/{twitch_user_id}/old_names",
+    tag = "users",
+    security(("api_key" = []), ("bearer" = [])),
+    params(
+        ("twitch_user_id" = String, Path, description = "twitch id of user"),
+    ),
+    responses((
+        status = 200,
+        description = "Names user had before, the most recent first",
+        body = [dto::UserOldName]
+    )),
+)]
+#[get("/users/<twitch_user_id>/old_names")]
+pub async fn get_old_names(
+    _auth: Authorized<ReadModeration>,
+    db: Db,
+    twitch_user_id: String,
+) -> ApiResult<Vec<dto::UserOldName>> {
+    let old_names = db
+        .run(move |db_conn| {
+            let user = seen_user(db_conn, twitch_user_id)?;
+
+            Ok(users_old_names::get_by_user_id(db_conn, user.id)?)
+        })
+        .await?;
+
+    Ok(Json(
+        old_names.into_iter().map(dto::UserOldName::from).collect(),
+    ))
+}
+
+fn seen_user(db_conn: &PgConnection, twitch_user_id: String) -> Result<User, ApiError> {
+    match users::get_user_by_user_id(&twitch_user_id, db_conn)? {
+        Some(user) => Ok(user),
+        None => Err(ApiError::UserNotFound(twitch_user_id)),
+    }
+}
@@ -1,27 +1,113 @@
 extern crate config as configlib;
 
-use std::{error::Error, fmt::Display};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::Display,
+    net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4},
+    path::Path,
+};
 
 use derivative::Derivative;
-use error_stack::IntoReport;
+use error_stack::{IntoReport, Report, ResultExt};
+use lazy_static::lazy_static;
 use log::LevelFilter;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
 use serde_derive::Deserialize;
-use tokio::sync::RwLock;
+use tokio::sync::{watch, RwLock};
+
+use crate::models::channel::ChannelPolicy;
+
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// characters left as they are in parts of database url, the unreserved ones from RFC 3986
+const URL_COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+const SSL_MODES: [&str; 6] = [
+    "disable",
+    "allow",
+    "prefer",
+    "require",
+    "verify-ca",
+    "verify-full",
+];
+
+const DEFAULT_API_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+const DEFAULT_HEALTH_SERVER_ADDRESS: SocketAddr =
+    SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 9184));
+
+/// S3 takes at most 5 GiB in single PUT, archive files are uploaded whole without multipart upload
+pub const S3_MAX_PUT_BYTES: u64 = 5 * 1024 * 1024 * 1024;
+
+/// Upper bound of one row in archive file. Message text has at most 500 characters, 2000 bytes
+/// in UTF-8, and the other columns with parquet overhead stay under the rest.
+pub const MAX_ARCHIVED_ROW_BYTES: u64 = 4 * 1024;
 
 // TODO try https://crates.io/crates/const-default instead
 pub static CONFIG: RwLock<Config> = RwLock::const_new(Config::const_default());
 
+lazy_static! {
+    /// notified after every successful reload
+    static ref CONFIG_UPDATES: watch::Sender<()> = watch::channel(()).0;
+}
+
 #[derive(Deserialize, Derivative)]
 #[derivative(Default)]
 pub struct Config {
     pub database: DatabaseConfig,
     // TODO change this back to twitch_api - probably will need to change config lib
     pub twitchapi: TwitchApi,
-    pub channels: Vec<String>,
+    /// http server of the api crate
+    #[serde(default)]
+    pub api: ApiConfig,
+    /// bot account used to log into irc, anonymous login when missing
+    pub irc: Option<IrcConfig>,
+    /// splits channels between collector instances, every instance joins all channels when missing
+    pub sharding: Option<ShardingConfig>,
+    /// collector prunes data of channels with `retention_days` on schedule only when present
+    pub retention: Option<RetentionConfig>,
+    /// where `twitch_collector --cold-archive` exports old partitions of messages
+    pub cold_archive: Option<ColdArchiveConfig>,
+    /// channels to collect from, each either just a name or a table with its policy
+    pub channels: Vec<ChannelConfig>,
     #[serde(default)]
     pub log_level: LogLevelFilter,
+    /// where log goes and levels of single modules
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// how many times saving message or room state is retried after serialization failure or
+    /// deadlock
     #[derivative(Default(value = "3"))]
     pub database_op_retry_limit: u8,
+    /// how often helix is asked whether channels are live
+    #[serde(default = "default_stream_poll_interval_secs")]
+    #[derivative(Default(value = "60"))]
+    pub stream_poll_interval_secs: u64,
+    /// how often channel names are checked against helix to catch renames
+    #[serde(default = "default_channel_reconcile_interval_secs")]
+    #[derivative(Default(value = "600"))]
+    pub channel_reconcile_interval_secs: u64,
+    /// address of http listener with /healthz, /readyz and /metrics
+    #[serde(default = "default_health_server_address")]
+    #[derivative(Default(value = "DEFAULT_HEALTH_SERVER_ADDRESS"))]
+    pub health_server_address: SocketAddr,
+    /// how long shutdown waits for messages that are still being saved
+    #[serde(default = "default_shutdown_timeout_secs")]
+    #[derivative(Default(value = "10"))]
+    pub shutdown_timeout_secs: u64,
+    /// how often collector records that it's still recording joined channels
+    #[serde(default = "default_coverage_heartbeat_interval_secs")]
+    #[derivative(Default(value = "30"))]
+    pub coverage_heartbeat_interval_secs: u64,
+    /// monthly partitions of messages are created this many months ahead
+    #[serde(default = "default_message_partitions_ahead_months")]
+    #[derivative(Default(value = "3"))]
+    pub message_partitions_ahead_months: u32,
 }
 
 impl Config {
@@ -31,20 +117,330 @@ impl Config {
             twitchapi: TwitchApi {
                 clientid: String::new(),
                 clientsecret: String::new(),
+                clientsecret_file: None,
             },
+            api: ApiConfig::const_default(),
+            irc: None,
+            sharding: None,
+            retention: None,
+            cold_archive: None,
             channels: vec![],
             log_level: LogLevelFilter::const_default(),
+            logging: LoggingConfig::const_default(),
             database_op_retry_limit: 3,
+            stream_poll_interval_secs: 60,
+            channel_reconcile_interval_secs: 600,
+            health_server_address: DEFAULT_HEALTH_SERVER_ADDRESS,
+            shutdown_timeout_secs: 10,
+            coverage_heartbeat_interval_secs: 30,
+            message_partitions_ahead_months: 3,
+        }
+    }
+}
+
+impl Config {
+    /// Collects every problem with config instead of stopping at the first one
+    pub fn validate(&self) -> error_stack::Result<(), ConfigError> {
+        let mut problems = vec![];
+
+        if self.twitchapi.clientid.is_empty() {
+            problems.push("twitchapi.clientid is empty".to_owned());
+        }
+        if self.twitchapi.clientsecret.is_empty() {
+            problems.push("twitchapi.clientsecret is empty".to_owned());
+        }
+
+        if self.database.url.is_empty() {
+            problems.push("database.url is empty".to_owned());
+        }
+
+        if self.database.is_full_url() {
+            if self.database.port.is_some() || self.database.sslmode.is_some() {
+                problems.push(
+                    "database.port and database.sslmode can't be used with full database.url, put them into the url"
+                        .to_owned(),
+                );
+            }
+        } else {
+            for (field, value) in [
+                ("database.username", &self.database.username),
+                ("database.db", &self.database.db),
+            ] {
+                if value.is_empty() {
+                    problems.push(format!("{field} is empty"));
+                }
+            }
+
+            if let Some(sslmode) = &self.database.sslmode {
+                if !SSL_MODES.contains(&sslmode.as_str()) {
+                    problems.push(format!(
+                        "database.sslmode \"{sslmode}\" isn't one of {}",
+                        SSL_MODES.join(", ")
+                    ));
+                }
+            }
+        }
+
+        for (position, channel) in self.channels.iter().enumerate() {
+            let name = &channel.name;
+
+            if !is_valid_login(name) {
+                problems.push(format!(
+                    "channels: \"{name}\" isn't valid twitch login (1-25 of a-z, 0-9 and _)"
+                ));
+            }
+            if self.channels[..position]
+                .iter()
+                .any(|other| other.name == *name)
+            {
+                problems.push(format!("channels: \"{name}\" is listed more than once"));
+            }
+            if matches!(channel.policy.retention_days, Some(days) if days <= 0) {
+                problems.push(format!(
+                    "channels: retention_days of \"{name}\" has to be greater than 0, remove it to keep everything"
+                ));
+            }
+            if channel.policy.stored_events.is_empty() && !channel.policy.paused {
+                problems.push(format!(
+                    "channels: \"{name}\" doesn't store any event, pause it instead"
+                ));
+            }
+        }
+
+        for (field, value) in [
+            ("stream_poll_interval_secs", self.stream_poll_interval_secs),
+            (
+                "channel_reconcile_interval_secs",
+                self.channel_reconcile_interval_secs,
+            ),
+            (
+                "coverage_heartbeat_interval_secs",
+                self.coverage_heartbeat_interval_secs,
+            ),
+        ] {
+            if value == 0 {
+                problems.push(format!("{field} has to be greater than 0"));
+            }
+        }
+
+        if self.message_partitions_ahead_months == 0 {
+            problems.push(
+                "message_partitions_ahead_months has to be greater than 0, messages of next month would have nowhere to go"
+                    .to_owned(),
+            );
+        }
+
+        if self.api.db_pool_size == 0 {
+            problems.push("api.db_pool_size has to be greater than 0".to_owned());
+        }
+        if self.api.max_page_size <= 0 {
+            problems.push("api.max_page_size has to be greater than 0".to_owned());
+        }
+        if self.api.max_time_range_hours <= 0 {
+            problems.push("api.max_time_range_hours has to be greater than 0".to_owned());
+        }
+
+        if let Some(tls) = &self.api.tls {
+            for (field, path) in [("api.tls.certs", &tls.certs), ("api.tls.key", &tls.key)] {
+                if !Path::new(path).is_file() {
+                    problems.push(format!("{field}: file \"{path}\" doesn't exist"));
+                }
+            }
+        }
+
+        if let Some(file) = &self.logging.file {
+            if file.path.is_empty() {
+                problems.push("logging.file.path is empty".to_owned());
+            }
+            if file.max_size_mb == 0 {
+                problems.push("logging.file.max_size_mb has to be greater than 0".to_owned());
+            }
+        }
+        if self.logging.modules.contains_key("") {
+            problems.push("logging.modules has module with empty name".to_owned());
+        }
+
+        if let Some(irc) = &self.irc {
+            if !is_valid_login(&irc.login) {
+                problems.push(format!(
+                    "irc.login \"{}\" isn't valid twitch login",
+                    irc.login
+                ));
+            }
+            if irc.token_file.is_empty() {
+                problems.push("irc.token_file is empty".to_owned());
+            }
+        }
+
+        if let Some(sharding) = &self.sharding {
+            if sharding.instance_id.as_deref() == Some("") {
+                problems.push("sharding.instance_id is empty, remove it to use default".to_owned());
+            }
+            if sharding.lease_renew_interval_secs == 0 {
+                problems
+                    .push("sharding.lease_renew_interval_secs has to be greater than 0".to_owned());
+            }
+            if sharding.lease_renew_interval_secs >= sharding.lease_duration_secs {
+                problems.push(
+                    "sharding.lease_renew_interval_secs has to be shorter than lease_duration_secs"
+                        .to_owned(),
+                );
+            }
+        }
+
+        if let Some(retention) = &self.retention {
+            if retention.interval_secs == 0 {
+                problems.push("retention.interval_secs has to be greater than 0".to_owned());
+            }
+            if retention.batch_size <= 0 {
+                problems.push("retention.batch_size has to be greater than 0".to_owned());
+            }
+            if retention.archive_dir.as_deref() == Some("") {
+                problems.push("retention.archive_dir is empty, remove it to only delete".to_owned());
+            }
+        }
+
+        if let Some(cold_archive) = &self.cold_archive {
+            if cold_archive.older_than_days == 0 {
+                problems.push("cold_archive.older_than_days has to be greater than 0".to_owned());
+            }
+            if cold_archive.rows_per_file <= 0 {
+                problems.push("cold_archive.rows_per_file has to be greater than 0".to_owned());
+            }
+
+            match (&cold_archive.dir, &cold_archive.s3) {
+                (Some(_), Some(_)) => problems
+                    .push("cold_archive: set either dir or [cold_archive.s3], not both".to_owned()),
+                (None, None) => problems
+                    .push("cold_archive: set dir or [cold_archive.s3] to archive into".to_owned()),
+                (Some(dir), None) if dir.is_empty() => {
+                    problems.push("cold_archive.dir is empty".to_owned())
+                }
+                _ => {}
+            }
+
+            if let Some(s3) = &cold_archive.s3 {
+                let max_rows_per_file = S3_MAX_PUT_BYTES / MAX_ARCHIVED_ROW_BYTES;
+                if cold_archive.rows_per_file as u64 > max_rows_per_file {
+                    problems.push(format!(
+                        "cold_archive.rows_per_file can be at most {max_rows_per_file} with \
+                         [cold_archive.s3], files are uploaded with single PUT of at most 5 GiB"
+                    ));
+                }
+                if !s3.endpoint.starts_with("http://") && !s3.endpoint.starts_with("https://") {
+                    problems.push(format!(
+                        "cold_archive.s3.endpoint \"{}\" has to start with http:// or https://",
+                        s3.endpoint
+                    ));
+                }
+                if s3.bucket.is_empty() {
+                    problems.push("cold_archive.s3.bucket is empty".to_owned());
+                }
+                if s3.access_key.is_empty() {
+                    problems.push("cold_archive.s3.access_key is empty".to_owned());
+                }
+                if s3.secret_key.is_empty() {
+                    problems.push("cold_archive.s3.secret_key is empty".to_owned());
+                }
+            }
+        }
+
+        if !problems.is_empty() {
+            return Err(Report::new(ConfigError::Invalid(problems)));
+        }
+
+        Ok(())
+    }
+}
+
+/// Twitch logins are 1-25 characters long and made of lowercase letters, digits and underscores
+fn is_valid_login(login: &str) -> bool {
+    (1..=25).contains(&login.len())
+        && login
+            .bytes()
+            .all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'_')
+}
+
+fn default_stream_poll_interval_secs() -> u64 {
+    60
+}
+
+fn default_channel_reconcile_interval_secs() -> u64 {
+    600
+}
+
+fn default_health_server_address() -> SocketAddr {
+    DEFAULT_HEALTH_SERVER_ADDRESS
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    10
+}
+
+fn default_coverage_heartbeat_interval_secs() -> u64 {
+    30
+}
+
+fn default_message_partitions_ahead_months() -> u32 {
+    3
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(from = "ChannelEntry")]
+pub struct ChannelConfig {
+    pub name: String,
+    pub policy: ChannelPolicy,
+}
+
+impl ChannelConfig {
+    /// Channels that should be joined, so the ones that aren't paused
+    pub fn active(channels: &[ChannelConfig]) -> impl Iterator<Item = &ChannelConfig> {
+        channels.iter().filter(|channel| !channel.policy.paused)
+    }
+}
+
+/// `channels` can mix plain names (default policy) and tables with policy
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ChannelEntry {
+    Name(String),
+    Table {
+        name: String,
+        #[serde(flatten)]
+        policy: ChannelPolicy,
+    },
+}
+
+impl From<ChannelEntry> for ChannelConfig {
+    fn from(entry: ChannelEntry) -> Self {
+        match entry {
+            ChannelEntry::Name(name) => Self {
+                name,
+                policy: ChannelPolicy::default(),
+            },
+            ChannelEntry::Table { name, policy } => Self { name, policy },
         }
     }
 }
 
 #[derive(Default, Deserialize)]
 pub struct DatabaseConfig {
+    /// host of the database, or full `postgres://` url which is then used as it is
     pub url: String,
+    #[serde(default)]
     pub username: String,
+    #[serde(default)]
     pub password: String,
+    #[serde(default)]
     pub db: String,
+    /// postgres default port is used when missing
+    pub port: Option<u16>,
+    /// one of disable, allow, prefer, require, verify-ca, verify-full
+    pub sslmode: Option<String>,
+    /// file with password (e.g. docker or kubernetes secret), overrides `password`
+    pub password_file: Option<String>,
+    /// file with full database url, overrides `url`
+    pub url_file: Option<String>,
 }
 
 impl DatabaseConfig {
@@ -54,8 +450,46 @@ impl DatabaseConfig {
             username: String::new(),
             password: String::new(),
             db: String::new(),
+            port: None,
+            sslmode: None,
+            password_file: None,
+            url_file: None,
         }
     }
+
+    fn is_full_url(&self) -> bool {
+        self.url.starts_with("postgres://") || self.url.starts_with("postgresql://")
+    }
+
+    /// Url for connecting to database, username, password and db are percent-encoded so they
+    /// can contain any character
+    pub fn connection_url(&self) -> String {
+        if self.is_full_url() {
+            return self.url.clone();
+        }
+
+        let mut url = format!(
+            "postgres://{}:{}@{}",
+            utf8_percent_encode(&self.username, URL_COMPONENT),
+            utf8_percent_encode(&self.password, URL_COMPONENT),
+            self.url,
+        );
+
+        if let Some(port) = self.port {
+            url.push_str(&format!(":{port}"));
+        }
+
+        url.push_str(&format!(
+            "/{}",
+            utf8_percent_encode(&self.db, URL_COMPONENT)
+        ));
+
+        if let Some(sslmode) = &self.sslmode {
+            url.push_str(&format!("?sslmode={sslmode}"));
+        }
+
+        url
+    }
 }
 
 #[derive(Default, Deserialize)]
@@ -63,7 +497,288 @@ pub struct TwitchApi {
     // TODO change this back to twitch_api - probably will need to change config lib
     pub clientid: String,
     // TODO change this back to twitch_api - probably will need to change config lib
+    #[serde(default)]
     pub clientsecret: String,
+    /// file with client secret (e.g. docker or kubernetes secret), overrides `clientsecret`
+    pub clientsecret_file: Option<String>,
+}
+
+#[derive(Deserialize, Derivative)]
+#[derivative(Default)]
+pub struct ApiConfig {
+    #[serde(default = "default_api_address")]
+    #[derivative(Default(value = "DEFAULT_API_ADDRESS"))]
+    pub address: IpAddr,
+    #[serde(default = "default_api_port")]
+    #[derivative(Default(value = "8000"))]
+    pub port: u16,
+    /// rocket picks number of workers from cpu count when missing
+    pub workers: Option<u16>,
+    /// max number of database connections
+    #[serde(default = "default_api_db_pool_size")]
+    #[derivative(Default(value = "10"))]
+    pub db_pool_size: u32,
+    /// https is used only when set
+    pub tls: Option<TlsConfig>,
+    /// public data can be read without api key, the rest always needs one
+    #[serde(default = "default_api_public_read")]
+    #[derivative(Default(value = "true"))]
+    pub public_read: bool,
+    /// requests per minute of one api key, or of one ip for requests without key, 0 turns it off
+    #[serde(default = "default_api_rate_limit_per_minute")]
+    #[derivative(Default(value = "120"))]
+    pub rate_limit_per_minute: u32,
+    /// ip of requests is taken from `X-Real-IP` header, only for api behind proxy that sets it
+    #[serde(default)]
+    pub trust_x_real_ip: bool,
+    /// most messages one request gets
+    #[serde(default = "default_api_max_page_size")]
+    #[derivative(Default(value = "1000"))]
+    pub max_page_size: i64,
+    /// longest time range messages can be requested for
+    #[serde(default = "default_api_max_time_range_hours")]
+    #[derivative(Default(value = "24"))]
+    pub max_time_range_hours: i64,
+    /// queries of api running longer are cancelled so they can't stall database collector writes to,
+    /// 0 turns it off
+    #[serde(default = "default_api_statement_timeout_ms")]
+    #[derivative(Default(value = "5000"))]
+    pub statement_timeout_ms: u64,
+}
+
+impl ApiConfig {
+    const fn const_default() -> Self {
+        Self {
+            address: DEFAULT_API_ADDRESS,
+            port: 8000,
+            workers: None,
+            db_pool_size: 10,
+            tls: None,
+            public_read: true,
+            rate_limit_per_minute: 120,
+            trust_x_real_ip: false,
+            max_page_size: 1000,
+            max_time_range_hours: 24,
+            statement_timeout_ms: 5000,
+        }
+    }
+}
+
+fn default_api_address() -> IpAddr {
+    DEFAULT_API_ADDRESS
+}
+
+fn default_api_port() -> u16 {
+    8000
+}
+
+fn default_api_db_pool_size() -> u32 {
+    10
+}
+
+fn default_api_public_read() -> bool {
+    true
+}
+
+fn default_api_rate_limit_per_minute() -> u32 {
+    120
+}
+
+fn default_api_max_page_size() -> i64 {
+    1000
+}
+
+fn default_api_max_time_range_hours() -> i64 {
+    24
+}
+
+fn default_api_statement_timeout_ms() -> u64 {
+    5000
+}
+
+#[derive(Deserialize)]
+pub struct TlsConfig {
+    /// path to certificate chain in PEM format
+    pub certs: String,
+    /// path to private key in PEM format
+    pub key: String,
+}
+
+#[derive(Deserialize, Derivative)]
+#[derivative(Default)]
+pub struct LoggingConfig {
+    #[serde(default = "default_true")]
+    #[derivative(Default(value = "true"))]
+    pub stdout: bool,
+    #[serde(default)]
+    pub stdout_format: LogFormat,
+    /// rotating log files, log isn't written to file when missing
+    pub file: Option<LogFileConfig>,
+    /// levels of modules (log targets) overriding `log_level`, e.g. `twitch_irc = "Warn"`
+    #[serde(default)]
+    pub modules: BTreeMap<String, LogLevelFilter>,
+    /// every n-th received irc message is logged at Debug level, 0 logs none
+    #[serde(default = "default_message_sample_rate")]
+    #[derivative(Default(value = "100"))]
+    pub message_sample_rate: u64,
+}
+
+impl LoggingConfig {
+    const fn const_default() -> Self {
+        Self {
+            stdout: true,
+            stdout_format: LogFormat::Text,
+            file: None,
+            modules: BTreeMap::new(),
+            message_sample_rate: 100,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_message_sample_rate() -> u64 {
+    100
+}
+
+#[derive(Deserialize)]
+pub struct LogFileConfig {
+    pub path: String,
+    #[serde(default)]
+    pub format: LogFormat,
+    /// file is rotated once it would grow over this size
+    #[serde(default = "default_log_max_size_mb")]
+    pub max_size_mb: u64,
+    /// number of rotated files kept next to the current one as `<path>.1` (newest) to `<path>.<keep>`
+    #[serde(default = "default_log_keep")]
+    pub keep: u32,
+}
+
+fn default_log_max_size_mb() -> u64 {
+    50
+}
+
+fn default_log_keep() -> u32 {
+    5
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+pub enum LogFormat {
+    /// human readable line
+    #[default]
+    Text,
+    /// one json object per line, fields of the record are its keys
+    Json,
+}
+
+#[derive(Deserialize)]
+pub struct IrcConfig {
+    /// login of the bot account
+    pub login: String,
+    #[serde(default)]
+    pub token_storage: TokenStorageKind,
+    /// used only with `TokenStorageKind::File`
+    #[serde(default = "default_token_file")]
+    pub token_file: String,
+}
+
+fn default_token_file() -> String {
+    "irc_token.json".to_owned()
+}
+
+#[derive(Deserialize)]
+pub struct ShardingConfig {
+    /// has to be unique among instances, `$HOSTNAME-<pid>` when missing
+    pub instance_id: Option<String>,
+    /// channels of instance that didn't renew its leases for this long are taken over by others
+    #[serde(default = "default_lease_duration_secs")]
+    pub lease_duration_secs: u64,
+    /// how often leases are renewed and channels rebalanced between instances
+    #[serde(default = "default_lease_renew_interval_secs")]
+    pub lease_renew_interval_secs: u64,
+}
+
+fn default_lease_duration_secs() -> u64 {
+    60
+}
+
+fn default_lease_renew_interval_secs() -> u64 {
+    15
+}
+
+#[derive(Deserialize, Derivative)]
+#[derivative(Default)]
+pub struct RetentionConfig {
+    /// how often expired data is pruned
+    #[serde(default = "default_retention_interval_secs")]
+    #[derivative(Default(value = "3600"))]
+    pub interval_secs: u64,
+    /// messages deleted in one transaction
+    #[serde(default = "default_retention_batch_size")]
+    #[derivative(Default(value = "5000"))]
+    pub batch_size: i64,
+    /// pruned messages are appended to `<archive_dir>/<channel>/<date>.jsonl` before they're
+    /// deleted, they're only deleted when missing
+    pub archive_dir: Option<String>,
+}
+
+fn default_retention_interval_secs() -> u64 {
+    3600
+}
+
+fn default_retention_batch_size() -> i64 {
+    5000
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ColdArchiveConfig {
+    /// monthly partitions are archived once their whole month is older than this
+    pub older_than_days: u32,
+    /// rows written to one parquet file, whole file is kept in memory until it's written
+    #[serde(default = "default_cold_archive_rows_per_file")]
+    pub rows_per_file: i64,
+    /// files go to `<dir>/messages/<yyyy-mm>/part-<n>.parquet`
+    pub dir: Option<String>,
+    /// files go to S3 compatible bucket (e.g. MinIO) instead of `dir`
+    pub s3: Option<S3Config>,
+}
+
+fn default_cold_archive_rows_per_file() -> i64 {
+    100_000
+}
+
+#[derive(Deserialize, Clone)]
+pub struct S3Config {
+    /// e.g. `https://s3.eu-central-1.amazonaws.com` or `http://127.0.0.1:9000`, bucket is
+    /// addressed by path
+    pub endpoint: String,
+    pub bucket: String,
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    /// prepended to keys of objects, e.g. `twitch/`
+    #[serde(default)]
+    pub prefix: String,
+    pub access_key: String,
+    #[serde(default)]
+    pub secret_key: String,
+    /// file with secret key, overrides `secret_key`
+    pub secret_key_file: Option<String>,
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".to_owned()
+}
+
+/// Where user access token of the bot account is kept between refreshes
+#[derive(Deserialize, Default, Clone, Copy)]
+pub enum TokenStorageKind {
+    /// `irc_tokens` table
+    #[default]
+    Database,
+    /// json file at `IrcConfig::token_file`
+    File,
 }
 
 #[repr(usize)]
@@ -117,6 +832,10 @@ pub enum ConfigError {
     MissingField(FieldName),
     FileParseError(ParseErrorCause),
     FieldTypeError(Option<FieldName>),
+    /// every problem found by `Config::validate`
+    Invalid(Vec<String>),
+    /// database or twitch api couldn't be reached with configured values
+    Unreachable(Vec<String>),
     Other(OptionalMessage),
 }
 
@@ -132,6 +851,20 @@ impl Display for ConfigError {
                 "Field {} has wrong value",
                 field.as_ref().map(|v| &v[..]).unwrap_or_default()
             ),
+            Self::Invalid(problems) => {
+                write!(f, "Config has {} problem(s):", problems.len())?;
+                for problem in problems {
+                    write!(f, "\n  - {problem}")?;
+                }
+                Ok(())
+            }
+            Self::Unreachable(problems) => {
+                write!(f, "Couldn't reach {} service(s):", problems.len())?;
+                for problem in problems {
+                    write!(f, "\n  - {problem}")?;
+                }
+                Ok(())
+            }
             Self::Other(msg) => match msg {
                 Some(msg) => write!(f, "Config error: {msg}"),
                 None => write!(
@@ -183,16 +916,41 @@ pub fn load_blocking() -> error_stack::Result<(), ConfigError> {
     Ok(())
 }
 
+/// Loads config file again and swaps it in only if it's valid, so broken edit keeps the old one
+pub async fn reload() -> error_stack::Result<(), ConfigError> {
+    let config = load_config()?;
+
+    *CONFIG.write().await = config;
+    // error only means nobody is subscribed
+    let _ = CONFIG_UPDATES.send(());
+
+    Ok(())
+}
+
+/// Returns receiver that's notified every time config is reloaded
+pub fn subscribe() -> watch::Receiver<()> {
+    CONFIG_UPDATES.subscribe()
+}
+
 fn load_config() -> error_stack::Result<Config, ConfigError> {
+    // every value can come from environment instead, so missing file is fine on its own
     let config = configlib::Config::builder()
-        .add_source(configlib::File::with_name("config.toml"))
+        .add_source(configlib::File::with_name(CONFIG_FILE).required(false))
         .add_source(
             configlib::Environment::with_prefix("TCH")
                 .separator("_")
                 .try_parsing(true),
         )
+        // keys with underscore in their name need double underscore between sections,
+        // e.g. TCH_DATABASE__PASSWORD_FILE
+        .add_source(
+            configlib::Environment::with_prefix("TCH")
+                .prefix_separator("_")
+                .separator("__")
+                .try_parsing(true),
+        )
         .build()
-        .into_report(); // todo not error out when config.toml is not present
+        .into_report();
 
     let config = config.map_err(|err| {
         let ctx = err.current_context().into();
@@ -208,5 +966,247 @@ fn load_config() -> error_stack::Result<Config, ConfigError> {
         err.change_context(ctx)
     });
 
-    config
+    let mut config = if Path::new(CONFIG_FILE).exists() {
+        config?
+    } else {
+        config.attach_printable_lazy(|| {
+            format!("{CONFIG_FILE} wasn't found in working directory and environment (TCH_*) doesn't have every value")
+        })?
+    };
+
+    read_secret_files(&mut config)?;
+    config.validate()?;
+
+    Ok(config)
+}
+
+/// Replaces values with contents of their `*_file` counterparts
+fn read_secret_files(config: &mut Config) -> error_stack::Result<(), ConfigError> {
+    let mut problems = vec![];
+
+    let mut secrets = vec![
+        (
+            "database.password_file",
+            &config.database.password_file,
+            &mut config.database.password,
+        ),
+        (
+            "database.url_file",
+            &config.database.url_file,
+            &mut config.database.url,
+        ),
+        (
+            "twitchapi.clientsecret_file",
+            &config.twitchapi.clientsecret_file,
+            &mut config.twitchapi.clientsecret,
+        ),
+    ];
+    if let Some(s3) = config
+        .cold_archive
+        .as_mut()
+        .and_then(|archive| archive.s3.as_mut())
+    {
+        secrets.push((
+            "cold_archive.s3.secret_key_file",
+            &s3.secret_key_file,
+            &mut s3.secret_key,
+        ));
+    }
+
+    for (field, path, value) in secrets {
+        let Some(path) = path else {
+            continue;
+        };
+
+        match std::fs::read_to_string(path) {
+            // secrets usually end with newline added by editor or `echo`
+            Ok(content) => *value = content.trim_end_matches(['\r', '\n']).to_owned(),
+            Err(err) => problems.push(format!("{field}: couldn't read \"{path}\": {err}")),
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(Report::new(ConfigError::Invalid(problems)));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid_config() -> Config {
+        let mut config = Config::default();
+
+        config.twitchapi.clientid = "client".to_owned();
+        config.twitchapi.clientsecret = "secret".to_owned();
+        config.database.url = "localhost".to_owned();
+        config.database.username = "collector".to_owned();
+        config.database.db = "chat".to_owned();
+        config.channels = vec![ChannelConfig {
+            name: "some_channel".to_owned(),
+            policy: ChannelPolicy::default(),
+        }];
+
+        config
+    }
+
+    fn problems(config: &Config) -> Vec<String> {
+        match config.validate() {
+            Ok(()) => vec![],
+            Err(report) => match report.current_context() {
+                ConfigError::Invalid(problems) => problems.clone(),
+                other => panic!("unexpected error {other:?}"),
+            },
+        }
+    }
+
+    #[test]
+    fn defaults_with_credentials_are_valid() {
+        assert_eq!(problems(&valid_config()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn collects_every_problem() {
+        let mut config = valid_config();
+        config.twitchapi.clientid.clear();
+        config.database.db.clear();
+        config.stream_poll_interval_secs = 0;
+
+        assert_eq!(
+            problems(&config),
+            vec![
+                "twitchapi.clientid is empty",
+                "database.db is empty",
+                "stream_poll_interval_secs has to be greater than 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn full_database_url_doesnt_need_username_and_db() {
+        let mut config = valid_config();
+        config.database.url = "postgres://collector@localhost/chat".to_owned();
+        config.database.username.clear();
+        config.database.db.clear();
+
+        assert_eq!(problems(&config), Vec::<String>::new());
+
+        config.database.port = Some(5432);
+
+        assert_eq!(problems(&config).len(), 1);
+    }
+
+    #[test]
+    fn rejects_invalid_sslmode() {
+        let mut config = valid_config();
+        config.database.sslmode = Some("sometimes".to_owned());
+
+        assert_eq!(problems(&config).len(), 1);
+
+        config.database.sslmode = Some("verify-full".to_owned());
+
+        assert!(problems(&config).is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_and_duplicate_channels() {
+        let mut config = valid_config();
+        config.channels.push(ChannelConfig {
+            name: "Not-Valid".to_owned(),
+            policy: ChannelPolicy::default(),
+        });
+        config.channels.push(ChannelConfig {
+            name: "some_channel".to_owned(),
+            policy: ChannelPolicy {
+                stored_events: vec![],
+                ..ChannelPolicy::default()
+            },
+        });
+
+        assert_eq!(
+            problems(&config),
+            vec![
+                "channels: \"Not-Valid\" isn't valid twitch login (1-25 of a-z, 0-9 and _)",
+                "channels: \"some_channel\" is listed more than once",
+                "channels: \"some_channel\" doesn't store any event, pause it instead",
+            ]
+        );
+    }
+
+    #[test]
+    fn paused_channel_doesnt_have_to_store_events() {
+        let mut config = valid_config();
+        config.channels[0].policy.stored_events.clear();
+        config.channels[0].policy.paused = true;
+
+        assert!(problems(&config).is_empty());
+    }
+
+    #[test]
+    fn lease_has_to_outlive_renew_interval() {
+        let mut config = valid_config();
+        config.sharding = Some(ShardingConfig {
+            instance_id: None,
+            lease_duration_secs: 60,
+            lease_renew_interval_secs: 60,
+        });
+
+        assert_eq!(
+            problems(&config),
+            vec!["sharding.lease_renew_interval_secs has to be shorter than lease_duration_secs"]
+        );
+    }
+
+    #[test]
+    fn cold_archive_needs_exactly_one_target() {
+        let mut config = valid_config();
+        config.cold_archive = Some(ColdArchiveConfig {
+            older_than_days: 90,
+            rows_per_file: 1000,
+            dir: None,
+            s3: None,
+        });
+
+        assert_eq!(
+            problems(&config),
+            vec!["cold_archive: set dir or [cold_archive.s3] to archive into"]
+        );
+    }
+
+    #[test]
+    fn cold_archive_file_has_to_fit_into_single_s3_put() {
+        let mut config = valid_config();
+        config.cold_archive = Some(ColdArchiveConfig {
+            older_than_days: 90,
+            rows_per_file: 2_000_000,
+            dir: None,
+            s3: Some(S3Config {
+                endpoint: "http://127.0.0.1:9000".to_owned(),
+                bucket: "archive".to_owned(),
+                region: default_s3_region(),
+                prefix: String::new(),
+                access_key: "access".to_owned(),
+                secret_key: "secret".to_owned(),
+                secret_key_file: None,
+            }),
+        });
+
+        assert_eq!(
+            problems(&config),
+            vec![
+                "cold_archive.rows_per_file can be at most 1310720 with [cold_archive.s3], files \
+                 are uploaded with single PUT of at most 5 GiB"
+            ]
+        );
+    }
+
+    #[test]
+    fn login_rules() {
+        assert!(is_valid_login("some_channel_123"));
+        assert!(!is_valid_login(""));
+        assert!(!is_valid_login("UpperCase"));
+        assert!(!is_valid_login(&"a".repeat(26)));
+    }
 }
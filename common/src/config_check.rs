@@ -0,0 +1,70 @@
+use diesel::{Connection, PgConnection};
+use error_stack::Report;
+
+use crate::config::ConfigError;
+
+const TWITCH_TOKEN_URL: &str = "https://id.twitch.tv/oauth2/token";
+
+/// Checks that database and twitch api accept configured credentials, for `--check-config`
+pub async fn check_connectivity() -> error_stack::Result<(), ConfigError> {
+    let mut problems = vec![];
+
+    let db_url = crate::construct_db_url_async().await;
+    // connecting blocks until database answers or connection times out
+    match tokio::task::spawn_blocking(move || PgConnection::establish(&db_url).map(drop)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => problems.push(format!("database: {err}")),
+        Err(err) => problems.push(format!("database: connection check panicked: {err}")),
+    }
+
+    if let Err(problem) = check_twitch_api().await {
+        problems.push(format!("twitch api: {problem}"));
+    }
+
+    if !problems.is_empty() {
+        return Err(Report::new(ConfigError::Unreachable(problems)));
+    }
+
+    Ok(())
+}
+
+/// Same as [`check_connectivity`] for binaries without tokio runtime
+pub fn check_connectivity_blocking() -> error_stack::Result<(), ConfigError> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|err| Report::new(ConfigError::Other(Some(err.to_string()))))?;
+
+    runtime.block_on(check_connectivity())
+}
+
+/// Asks for app access token, which fails on wrong client id or secret
+async fn check_twitch_api() -> Result<(), String> {
+    let (client_id, client_secret) = {
+        let config = crate::get_config_async!().await;
+        (
+            config.twitchapi.clientid.clone(),
+            config.twitchapi.clientsecret.clone(),
+        )
+    };
+
+    let response = reqwest::Client::new()
+        .post(TWITCH_TOKEN_URL)
+        .form(&[
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+            ("grant_type", "client_credentials".to_owned()),
+        ])
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+
+        return Err(format!("{status}: {body}"));
+    }
+
+    Ok(())
+}
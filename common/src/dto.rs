@@ -0,0 +1,281 @@
+// Response models of the api. Database models carry internal ids and foreign keys, these are
+// what clients see. OpenAPI document served by api is derived from them, so changing them
+// changes the api.
+
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        self, channel::EventKind, collection_gap::GapReason, message::MsgType, resub::Tier,
+        user_erasure::ErasureMode,
+    },
+    services::messages::MessageWithUser,
+};
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct Channel {
+    #[schema(value_type = String, format = "uuid")]
+    pub uuid: Uuid,
+    pub twitch_channel_id: String,
+    pub channel_name: String,
+    /// first time collector joined the channel
+    pub collecting_since: Option<DateTime<Utc>>,
+    /// last time collector was known to be recording the channel
+    pub last_collected_at: Option<DateTime<Utc>>,
+    pub stored_events: Vec<EventKind>,
+    /// only metadata of messages is stored when false
+    pub store_text: bool,
+    /// data older than this is pruned, kept forever when none
+    pub retention_days: Option<i32>,
+    /// paused channels aren't joined
+    pub paused: bool,
+}
+
+impl From<models::channel::Channel> for Channel {
+    fn from(channel: models::channel::Channel) -> Self {
+        Self {
+            uuid: channel.uuid,
+            twitch_channel_id: channel.twitch_channel_id,
+            channel_name: channel.channel_name,
+            collecting_since: channel.collecting_since,
+            last_collected_at: channel.last_collected_at,
+            stored_events: channel.stored_events,
+            store_text: channel.store_text,
+            retention_days: channel.retention_days,
+            paused: channel.paused,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct User {
+    #[schema(value_type = String, format = "uuid")]
+    pub uuid: Uuid,
+    pub twitch_user_id: String,
+    /// latest name user was seen with
+    pub username: String,
+}
+
+impl From<models::user::User> for User {
+    fn from(user: models::user::User) -> Self {
+        Self {
+            uuid: user.uuid,
+            twitch_user_id: user.twitch_user_id,
+            username: user.username,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct UserOldName {
+    pub username: String,
+    /// when user was first seen with the name that replaced this one
+    pub first_time_with_new_name: DateTime<Utc>,
+}
+
+impl From<models::user_old_name::UserOldName> for UserOldName {
+    fn from(old_name: models::user_old_name::UserOldName) -> Self {
+        Self {
+            username: old_name.username,
+            first_time_with_new_name: old_name.first_time_with_new_name,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct Resub {
+    pub cumulative_month: i16,
+    pub tier: Tier,
+}
+
+impl From<models::resub::Resub> for Resub {
+    fn from(resub: models::resub::Resub) -> Self {
+        Self {
+            cumulative_month: resub.cumulative_month,
+            tier: resub.tier,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct Message {
+    /// increases in the order messages were received, used for paging
+    pub id: i64,
+    #[schema(value_type = String, format = "uuid")]
+    pub uuid: Uuid,
+    pub send_time: DateTime<Utc>,
+    pub msg_type: MsgType,
+    /// none when channel's policy doesn't allow storing text
+    pub msg: Option<String>,
+    pub bits: Option<i64>,
+    pub stream_session_id: Option<i32>,
+    /// none when user was erased and message was kept anonymized
+    pub user: Option<User>,
+    /// set for sub messages
+    pub resub: Option<Resub>,
+}
+
+impl From<MessageWithUser> for Message {
+    fn from((message, user, resub): MessageWithUser) -> Self {
+        Self {
+            id: message.id,
+            uuid: message.uuid,
+            send_time: message.send_time,
+            msg_type: message.msg_type,
+            msg: message.msg,
+            bits: message.bits,
+            stream_session_id: message.stream_session_id,
+            user: user.map(User::from),
+            resub: resub.map(Resub::from),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct StreamSession {
+    /// used in path of messages of the stream
+    pub id: i32,
+    #[schema(value_type = String, format = "uuid")]
+    pub uuid: Uuid,
+    pub twitch_stream_id: String,
+    pub title: String,
+    pub category_id: String,
+    pub category_name: String,
+    pub started_at: DateTime<Utc>,
+    /// none while the stream is live
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+impl From<models::stream_session::StreamSession> for StreamSession {
+    fn from(stream_session: models::stream_session::StreamSession) -> Self {
+        Self {
+            id: stream_session.id,
+            uuid: stream_session.uuid,
+            twitch_stream_id: stream_session.twitch_stream_id,
+            title: stream_session.title,
+            category_id: stream_session.category_id,
+            category_name: stream_session.category_name,
+            started_at: stream_session.started_at,
+            ended_at: stream_session.ended_at,
+        }
+    }
+}
+
+/// Chat modes that changed, the ones that didn't are none
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct RoomStateChange {
+    #[schema(value_type = String, format = "uuid")]
+    pub uuid: Uuid,
+    pub change_time: DateTime<Utc>,
+    pub emote_only: Option<bool>,
+    /// -1 when followers only mode was disabled
+    pub followers_only_minutes: Option<i32>,
+    pub r9k: Option<bool>,
+    /// 0 when slow mode was disabled
+    pub slow_mode_seconds: Option<i32>,
+    pub subscribers_only: Option<bool>,
+}
+
+impl From<models::room_state_change::RoomStateChange> for RoomStateChange {
+    fn from(change: models::room_state_change::RoomStateChange) -> Self {
+        Self {
+            uuid: change.uuid,
+            change_time: change.change_time,
+            emote_only: change.emote_only,
+            followers_only_minutes: change.followers_only_minutes,
+            r9k: change.r9k,
+            slow_mode_seconds: change.slow_mode_seconds,
+            subscribers_only: change.subscribers_only,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy)]
+pub struct TimeRange {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl From<models::collection_gap::TimeRange> for TimeRange {
+    fn from(range: models::collection_gap::TimeRange) -> Self {
+        Self {
+            start: range.start,
+            end: range.end,
+        }
+    }
+}
+
+/// Time when channel wasn't recorded although it was tracked
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct CollectionGap {
+    #[schema(value_type = String, format = "uuid")]
+    pub uuid: Uuid,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub reason: GapReason,
+}
+
+impl From<models::collection_gap::CollectionGap> for CollectionGap {
+    fn from(gap: models::collection_gap::CollectionGap) -> Self {
+        Self {
+            uuid: gap.uuid,
+            started_at: gap.started_at,
+            ended_at: gap.ended_at,
+            reason: gap.reason,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct Coverage {
+    /// time ranges there's data for
+    pub covered: Vec<TimeRange>,
+    pub gaps: Vec<CollectionGap>,
+}
+
+impl From<models::collection_gap::Coverage> for Coverage {
+    fn from(coverage: models::collection_gap::Coverage) -> Self {
+        Self {
+            covered: coverage.covered.into_iter().map(TimeRange::from).collect(),
+            gaps: coverage.gaps.into_iter().map(CollectionGap::from).collect(),
+        }
+    }
+}
+
+/// Audit record of erased user, it keeps only twitch user id of who was erased
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct UserErasure {
+    #[schema(value_type = String, format = "uuid")]
+    pub uuid: Uuid,
+    pub twitch_user_id: String,
+    pub mode: ErasureMode,
+    /// who asked for the erasure, e.g. name and uuid of api key
+    pub requested_by: String,
+    pub reason: Option<String>,
+    /// number of deleted or anonymized messages
+    pub messages: i64,
+    pub old_names: i64,
+    pub erased_at: DateTime<Utc>,
+    /// whether copies of the user in retention and cold archive files were removed, erasure
+    /// itself doesn't touch them
+    pub archives_processed: bool,
+}
+
+impl From<models::user_erasure::UserErasure> for UserErasure {
+    fn from(erasure: models::user_erasure::UserErasure) -> Self {
+        Self {
+            uuid: erasure.uuid,
+            twitch_user_id: erasure.twitch_user_id,
+            mode: erasure.mode,
+            requested_by: erasure.requested_by,
+            reason: erasure.reason,
+            messages: erasure.messages,
+            old_names: erasure.old_names,
+            erased_at: erasure.erased_at,
+            archives_processed: erasure.archives_processed,
+        }
+    }
+}
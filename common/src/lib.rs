@@ -1,3 +1,6 @@
+// diesel 1.4 derives and `table!` put impls inside functions, which newer compilers warn about
+#![allow(non_local_definitions)]
+
 #[macro_use]
 extern crate diesel;
 
@@ -5,6 +8,8 @@ extern crate diesel;
 extern crate anyhow;
 
 pub mod config;
+pub mod config_check;
+pub mod dto;
 pub mod models;
 pub mod schema;
 pub mod services;
@@ -14,25 +19,9 @@ pub mod services;
 // }
 
 pub fn construct_db_url_blocking() -> String {
-    let config = get_config_blocking!();
-
-    format!(
-        "postgres://{}:{}@{}/{}",
-        config.database.username.clone(),
-        config.database.password,
-        config.database.url.clone(),
-        config.database.db.clone(),
-    )
+    get_config_blocking!().database.connection_url()
 }
 
 pub async fn construct_db_url_async() -> String {
-    let config = get_config_async!().await;
-
-    format!(
-        "postgres://{}:{}@{}/{}",
-        config.database.username.clone(),
-        config.database.password,
-        config.database.url.clone(),
-        config.database.db.clone(),
-    )
+    get_config_async!().await.database.connection_url()
 }
@@ -0,0 +1,193 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use diesel::{
+    deserialize::FromSql,
+    pg::Pg,
+    serialize::ToSql,
+    types::{IsNull, VarChar},
+};
+use serde_derive::Serialize;
+use uuid::Uuid;
+
+use crate::schema::api_keys;
+
+/// What api key gives access to
+#[derive(Debug, AsExpression, FromSqlRow, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "VarChar"]
+pub enum ApiScope {
+    /// channels, streams, messages and coverage
+    ReadPublic,
+    /// room states and other moderation data, which must not be public
+    ReadModeration,
+    /// everything, including erasure of users
+    Admin,
+    /// bulk exports of data
+    Export,
+}
+
+impl ApiScope {
+    pub fn all() -> [Self; 4] {
+        [
+            Self::ReadPublic,
+            Self::ReadModeration,
+            Self::Admin,
+            Self::Export,
+        ]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ReadPublic => "read_public",
+            Self::ReadModeration => "read_moderation",
+            Self::Admin => "admin",
+            Self::Export => "export",
+        }
+    }
+}
+
+impl Display for ApiScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for ApiScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::all()
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| {
+                let scopes: Vec<&str> = Self::all().iter().map(ApiScope::as_str).collect();
+                format!(
+                    "unknown scope \"{s}\", possible scopes: {}",
+                    scopes.join(", ")
+                )
+            })
+    }
+}
+
+impl ToSql<VarChar, Pg> for ApiScope
+where
+    String: ToSql<VarChar, Pg>,
+{
+    fn to_sql<W: std::io::Write>(
+        &self,
+        out: &mut diesel::serialize::Output<W, Pg>,
+    ) -> diesel::serialize::Result {
+        <String as ToSql<VarChar, Pg>>::to_sql(&self.as_str().to_owned(), out)?;
+
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<VarChar, Pg> for ApiScope {
+    fn from_sql(
+        bytes: Option<&<Pg as diesel::backend::Backend>::RawValue>,
+    ) -> diesel::deserialize::Result<Self> {
+        let bytes = bytes.ok_or_else(|| anyhow!("no bytes given"))?;
+
+        match bytes {
+            b"read_public" => Ok(ApiScope::ReadPublic),
+            b"read_moderation" => Ok(ApiScope::ReadModeration),
+            b"admin" => Ok(ApiScope::Admin),
+            b"export" => Ok(ApiScope::Export),
+            _ => Err(anyhow!("Bytes given doesn't match ApiScope type"))?,
+        }
+    }
+}
+
+#[derive(Queryable, Serialize, Debug, Clone)]
+pub struct ApiKey {
+    pub id: i32,
+    pub uuid: Uuid,
+    /// who or what the key is for
+    pub name: String,
+    #[serde(skip)]
+    pub key_hash: String,
+    pub scopes: Vec<ApiScope>,
+    pub created_at: DateTime<Utc>,
+    /// revoked keys are kept, so it's known who had access
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    /// Admin key can do everything
+    pub fn allows(&self, scope: ApiScope) -> bool {
+        self.scopes.contains(&ApiScope::Admin) || self.scopes.contains(&scope)
+    }
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[table_name = "api_keys"]
+pub struct NewApiKey {
+    pub name: String,
+    pub key_hash: String,
+    pub scopes: Vec<ApiScope>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_with(scopes: Vec<ApiScope>) -> ApiKey {
+        ApiKey {
+            id: 1,
+            uuid: Uuid::nil(),
+            name: "test".to_owned(),
+            key_hash: String::new(),
+            scopes,
+            created_at: Utc::now(),
+            revoked_at: None,
+        }
+    }
+
+    #[test]
+    fn scope_parses_from_its_name() {
+        for scope in ApiScope::all() {
+            assert_eq!(scope.as_str().parse::<ApiScope>(), Ok(scope));
+        }
+    }
+
+    #[test]
+    fn unknown_scope_lists_possible_ones() {
+        let err = "Admin".parse::<ApiScope>().unwrap_err();
+
+        assert_eq!(
+            err,
+            "unknown scope \"Admin\", possible scopes: read_public, read_moderation, admin, export"
+        );
+    }
+
+    #[test]
+    fn key_allows_only_its_scopes() {
+        let key = key_with(vec![ApiScope::ReadPublic, ApiScope::Export]);
+
+        assert!(key.allows(ApiScope::ReadPublic));
+        assert!(key.allows(ApiScope::Export));
+        assert!(!key.allows(ApiScope::ReadModeration));
+        assert!(!key.allows(ApiScope::Admin));
+    }
+
+    #[test]
+    fn admin_key_allows_everything() {
+        let key = key_with(vec![ApiScope::Admin]);
+
+        for scope in ApiScope::all() {
+            assert!(key.allows(scope), "{scope}");
+        }
+    }
+
+    #[test]
+    fn key_without_scopes_allows_nothing() {
+        let key = key_with(vec![]);
+
+        for scope in ApiScope::all() {
+            assert!(!key.allows(scope), "{scope}");
+        }
+    }
+}
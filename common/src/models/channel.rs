@@ -1,4 +1,14 @@
-use crate::schema::channels;
+use crate::{models::message::MsgType, schema::channels};
+use chrono::{DateTime, Utc};
+use derivative::Derivative;
+use diesel::{
+    deserialize::FromSql,
+    pg::Pg,
+    serialize::ToSql,
+    types::{IsNull, VarChar},
+};
+use serde_derive::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 #[derive(Queryable, Debug, Clone)]
@@ -7,6 +17,33 @@ pub struct Channel {
     pub uuid: Uuid,
     pub twitch_channel_id: String,
     pub channel_name: String,
+    /// first time collector joined the channel
+    pub collecting_since: Option<DateTime<Utc>>,
+    /// last time collector was known to be recording the channel
+    pub last_collected_at: Option<DateTime<Utc>>,
+    pub stored_events: Vec<EventKind>,
+    /// only metadata of messages is stored when false
+    pub store_text: bool,
+    /// data older than this is pruned, kept forever when none
+    pub retention_days: Option<i32>,
+    /// paused channels aren't joined
+    pub paused: bool,
+}
+
+impl Channel {
+    pub fn policy(&self) -> ChannelPolicy {
+        ChannelPolicy {
+            stored_events: self.stored_events.clone(),
+            store_text: self.store_text,
+            retention_days: self.retention_days,
+            paused: self.paused,
+        }
+    }
+
+    /// Whether events of this kind should be saved
+    pub fn stores(&self, event: EventKind) -> bool {
+        !self.paused && self.stored_events.contains(&event)
+    }
 }
 
 #[derive(Insertable, Debug, Clone)]
@@ -15,3 +52,98 @@ pub struct NewChannel {
     pub twitch_channel_id: String,
     pub channel_name: String,
 }
+
+/// What is collected from a channel, set per channel in config
+#[derive(Deserialize, Derivative, Debug, Clone, PartialEq, Eq)]
+#[derivative(Default)]
+pub struct ChannelPolicy {
+    #[serde(default = "EventKind::all")]
+    #[derivative(Default(value = "EventKind::all()"))]
+    pub stored_events: Vec<EventKind>,
+    #[serde(default = "default_store_text")]
+    #[derivative(Default(value = "true"))]
+    pub store_text: bool,
+    #[serde(default)]
+    pub retention_days: Option<i32>,
+    #[serde(default)]
+    pub paused: bool,
+}
+
+fn default_store_text() -> bool {
+    true
+}
+
+#[derive(
+    Debug, AsExpression, FromSqlRow, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Eq,
+)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "VarChar"]
+pub enum EventKind {
+    Message,
+    Action,
+    Bits,
+    Sub,
+    RoomState,
+}
+
+impl EventKind {
+    pub fn all() -> Vec<Self> {
+        vec![
+            Self::Message,
+            Self::Action,
+            Self::Bits,
+            Self::Sub,
+            Self::RoomState,
+        ]
+    }
+}
+
+impl From<MsgType> for EventKind {
+    fn from(msg_type: MsgType) -> Self {
+        match msg_type {
+            MsgType::Message => Self::Message,
+            MsgType::Action => Self::Action,
+            MsgType::Bits => Self::Bits,
+            MsgType::Sub => Self::Sub,
+        }
+    }
+}
+
+impl ToSql<VarChar, Pg> for EventKind
+where
+    String: ToSql<VarChar, Pg>,
+{
+    fn to_sql<W: std::io::Write>(
+        &self,
+        out: &mut diesel::serialize::Output<W, Pg>,
+    ) -> diesel::serialize::Result {
+        let value = match self {
+            Self::Message => "message",
+            Self::Action => "action",
+            Self::Bits => "bits",
+            Self::Sub => "sub",
+            Self::RoomState => "room_state",
+        };
+
+        <String as ToSql<VarChar, Pg>>::to_sql(&value.to_owned(), out)?;
+
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<VarChar, Pg> for EventKind {
+    fn from_sql(
+        bytes: Option<&<Pg as diesel::backend::Backend>::RawValue>,
+    ) -> diesel::deserialize::Result<Self> {
+        let bytes = bytes.ok_or_else(|| anyhow!("no bytes given"))?;
+
+        match bytes {
+            b"message" => Ok(EventKind::Message),
+            b"action" => Ok(EventKind::Action),
+            b"bits" => Ok(EventKind::Bits),
+            b"sub" => Ok(EventKind::Sub),
+            b"room_state" => Ok(EventKind::RoomState),
+            _ => Err(anyhow!("Bytes given doesn't match EventKind type"))?,
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use chrono::{DateTime, Utc};
+
+use crate::schema::channel_leases;
+
+/// Channel recorded by collector instance `instance_id` until `expires_at`
+#[derive(Queryable, Debug, Clone)]
+pub struct ChannelLease {
+    pub id: i32,
+    pub channel_id: i32,
+    pub instance_id: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[table_name = "channel_leases"]
+pub struct NewChannelLease {
+    pub channel_id: i32,
+    pub instance_id: String,
+    pub expires_at: DateTime<Utc>,
+}
@@ -0,0 +1,19 @@
+use chrono::{DateTime, Utc};
+
+use crate::schema::channels_old_names;
+
+#[derive(Queryable, Debug, Clone)]
+pub struct ChannelOldName {
+    pub id: i32,
+    pub channel_id: i32,
+    pub channel_name: String,
+    pub first_time_with_new_name: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[table_name = "channels_old_names"]
+pub struct NewChannelOldName {
+    pub channel_id: i32,
+    pub channel_name: String,
+    pub first_time_with_new_name: DateTime<Utc>,
+}
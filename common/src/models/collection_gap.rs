@@ -0,0 +1,92 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    deserialize::FromSql,
+    pg::Pg,
+    serialize::ToSql,
+    types::{IsNull, VarChar},
+};
+use serde_derive::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::schema::collection_gaps;
+
+#[derive(
+    Debug, AsExpression, FromSqlRow, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Eq,
+)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "VarChar"]
+pub enum GapReason {
+    /// collector wasn't running
+    CollectorDown,
+    /// collector was running, but irc connection was lost or stalled
+    IrcDisconnected,
+}
+
+impl ToSql<VarChar, Pg> for GapReason
+where
+    String: ToSql<VarChar, Pg>,
+{
+    fn to_sql<W: std::io::Write>(
+        &self,
+        out: &mut diesel::serialize::Output<W, Pg>,
+    ) -> diesel::serialize::Result {
+        match self {
+            Self::CollectorDown => {
+                <String as ToSql<VarChar, Pg>>::to_sql(&"collector_down".to_owned(), out)?;
+            }
+            Self::IrcDisconnected => {
+                <String as ToSql<VarChar, Pg>>::to_sql(&"irc_disconnected".to_owned(), out)?;
+            }
+        };
+
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<VarChar, Pg> for GapReason {
+    fn from_sql(
+        bytes: Option<&<Pg as diesel::backend::Backend>::RawValue>,
+    ) -> diesel::deserialize::Result<Self> {
+        let bytes = bytes.ok_or_else(|| anyhow!("no bytes given"))?;
+
+        match bytes {
+            b"collector_down" => Ok(GapReason::CollectorDown),
+            b"irc_disconnected" => Ok(GapReason::IrcDisconnected),
+            _ => Err(anyhow!("Bytes given doesn't match GapReason type"))?,
+        }
+    }
+}
+
+/// Period during which nothing was recorded for a channel
+#[derive(Queryable, Serialize, Debug, Clone)]
+pub struct CollectionGap {
+    pub id: i32,
+    pub uuid: Uuid,
+    pub channel_id: i32,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub reason: GapReason,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[table_name = "collection_gaps"]
+pub struct NewCollectionGap {
+    pub channel_id: i32,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub reason: GapReason,
+}
+
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct TimeRange {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+/// Time ranges of a channel for which we have data, and gaps in between them
+#[derive(Serialize, Debug, Clone)]
+pub struct Coverage {
+    pub covered: Vec<TimeRange>,
+    pub gaps: Vec<CollectionGap>,
+}
@@ -0,0 +1,17 @@
+use chrono::{DateTime, Utc};
+
+use crate::schema::collector_instances;
+
+#[derive(Queryable, Debug, Clone)]
+pub struct CollectorInstance {
+    pub id: i32,
+    pub instance_id: String,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, AsChangeset, Debug, Clone)]
+#[table_name = "collector_instances"]
+pub struct NewCollectorInstance {
+    pub instance_id: String,
+    pub last_seen_at: DateTime<Utc>,
+}
@@ -0,0 +1,24 @@
+use chrono::{DateTime, Utc};
+
+use crate::schema::irc_tokens;
+
+#[derive(Queryable, Debug, Clone)]
+pub struct IrcToken {
+    pub id: i32,
+    pub login: String,
+    pub access_token: String,
+    pub refresh_token: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable, AsChangeset, Debug, Clone)]
+#[table_name = "irc_tokens"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct NewIrcToken {
+    pub login: String,
+    pub access_token: String,
+    pub refresh_token: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
@@ -5,11 +5,14 @@ use diesel::{
     serialize::ToSql,
     types::{IsNull, VarChar},
 };
+use serde_derive::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::schema::messages;
 
-#[derive(Debug, AsExpression, FromSqlRow, Clone, Copy)]
+#[derive(Debug, AsExpression, FromSqlRow, Serialize, Deserialize, ToSchema, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
 #[sql_type = "VarChar"]
 pub enum MsgType {
     /// normal message
@@ -65,29 +68,33 @@ impl FromSql<VarChar, Pg> for MsgType {
     }
 }
 
-#[derive(Queryable, Debug, Clone)]
+#[derive(Queryable, Serialize, Debug, Clone)]
 pub struct Message {
     pub id: i64,
     pub uuid: Uuid,
-    pub msg: String,
+    /// none when channel's policy doesn't allow storing text
+    pub msg: Option<String>,
     pub msg_type: MsgType,
-    pub user_id: i32,
+    /// none when user was erased and message was kept anonymized
+    pub user_id: Option<i32>,
     pub channel_id: i32,
     pub resub_id: Option<i32>,
     pub send_time: DateTime<Utc>,
     pub bits: Option<i64>,
+    pub stream_session_id: Option<i32>,
 }
 
 #[derive(Insertable, Debug, Clone)]
 #[table_name = "messages"]
 pub struct NewMessage {
-    pub msg: String,
+    pub msg: Option<String>,
     pub msg_type: MsgType,
-    pub user_id: i32,
+    pub user_id: Option<i32>,
     pub channel_id: i32,
     pub send_time: DateTime<Utc>,
     pub bits: Option<i64>,
     pub resub_id: Option<i32>,
+    pub stream_session_id: Option<i32>,
 }
 
 impl From<Message> for NewMessage {
@@ -100,6 +107,7 @@ impl From<Message> for NewMessage {
             send_time: message.send_time,
             bits: message.bits,
             resub_id: message.resub_id,
+            stream_session_id: message.stream_session_id,
         }
     }
 }
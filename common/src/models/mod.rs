@@ -1,5 +1,15 @@
+pub mod api_key;
 pub mod channel;
+pub mod channel_lease;
+pub mod channel_old_name;
+pub mod collection_gap;
+pub mod collector_instance;
+pub mod irc_token;
 pub mod message;
 pub mod resub;
+pub mod room_state_change;
+pub mod stream_session;
+pub mod stream_session_change;
 pub mod user;
+pub mod user_erasure;
 pub mod user_old_name;
@@ -5,16 +5,34 @@ use diesel::{
     sql_types::SmallInt,
     types::{FromSql, ToSql},
 };
+use serde_derive::{Deserialize, Serialize};
 use strum_macros::FromRepr;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow, FromRepr)]
+/// Serialized the way twitch sends it in `msg-param-sub-plan`
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    AsExpression,
+    FromSqlRow,
+    FromRepr,
+    Serialize,
+    Deserialize,
+    ToSchema,
+)]
 #[repr(i16)]
 #[sql_type = "SmallInt"]
 pub enum Tier {
     Prime = 0,
+    #[serde(rename = "1000")]
     One = 1,
+    #[serde(rename = "2000")]
     Two = 2,
+    #[serde(rename = "3000")]
     Three = 3,
 }
 
@@ -0,0 +1,35 @@
+use chrono::{DateTime, Utc};
+use serde_derive::Serialize;
+use uuid::Uuid;
+
+use crate::schema::room_state_changes;
+
+/// Single ROOMSTATE update of a channel.
+///
+/// Every mode field is `None` if the update didn't change that mode.
+#[derive(Queryable, Serialize, Debug, Clone)]
+pub struct RoomStateChange {
+    pub id: i32,
+    pub uuid: Uuid,
+    pub channel_id: i32,
+    pub change_time: DateTime<Utc>,
+    pub emote_only: Option<bool>,
+    /// -1 when followers only mode was disabled
+    pub followers_only_minutes: Option<i32>,
+    pub r9k: Option<bool>,
+    /// 0 when slow mode was disabled
+    pub slow_mode_seconds: Option<i32>,
+    pub subscribers_only: Option<bool>,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[table_name = "room_state_changes"]
+pub struct NewRoomStateChange {
+    pub channel_id: i32,
+    pub change_time: DateTime<Utc>,
+    pub emote_only: Option<bool>,
+    pub followers_only_minutes: Option<i32>,
+    pub r9k: Option<bool>,
+    pub slow_mode_seconds: Option<i32>,
+    pub subscribers_only: Option<bool>,
+}
@@ -0,0 +1,31 @@
+use chrono::{DateTime, Utc};
+use serde_derive::Serialize;
+use uuid::Uuid;
+
+use crate::schema::stream_sessions;
+
+/// Single live period of a channel, as seen by polling helix.
+#[derive(Queryable, Serialize, Debug, Clone)]
+pub struct StreamSession {
+    pub id: i32,
+    pub uuid: Uuid,
+    pub channel_id: i32,
+    pub twitch_stream_id: String,
+    pub title: String,
+    pub category_id: String,
+    pub category_name: String,
+    pub started_at: DateTime<Utc>,
+    /// `None` while the stream is live
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[table_name = "stream_sessions"]
+pub struct NewStreamSession {
+    pub channel_id: i32,
+    pub twitch_stream_id: String,
+    pub title: String,
+    pub category_id: String,
+    pub category_name: String,
+    pub started_at: DateTime<Utc>,
+}
@@ -0,0 +1,26 @@
+use chrono::{DateTime, Utc};
+use serde_derive::Serialize;
+use uuid::Uuid;
+
+use crate::schema::stream_session_changes;
+
+#[derive(Queryable, Serialize, Debug, Clone)]
+pub struct StreamSessionChange {
+    pub id: i32,
+    pub uuid: Uuid,
+    pub stream_session_id: i32,
+    pub change_time: DateTime<Utc>,
+    pub title: String,
+    pub category_id: String,
+    pub category_name: String,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[table_name = "stream_session_changes"]
+pub struct NewStreamSessionChange {
+    pub stream_session_id: i32,
+    pub change_time: DateTime<Utc>,
+    pub title: String,
+    pub category_id: String,
+    pub category_name: String,
+}
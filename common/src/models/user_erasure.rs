@@ -0,0 +1,89 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    deserialize::FromSql,
+    pg::Pg,
+    serialize::ToSql,
+    types::{IsNull, VarChar},
+};
+use serde_derive::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::schema::user_erasures;
+
+#[derive(
+    Debug, AsExpression, FromSqlRow, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Eq,
+)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "VarChar"]
+pub enum ErasureMode {
+    /// messages of the user are deleted
+    Delete,
+    /// text of messages is removed and they're kept without the user
+    Anonymize,
+}
+
+impl ToSql<VarChar, Pg> for ErasureMode
+where
+    String: ToSql<VarChar, Pg>,
+{
+    fn to_sql<W: std::io::Write>(
+        &self,
+        out: &mut diesel::serialize::Output<W, Pg>,
+    ) -> diesel::serialize::Result {
+        let value = match self {
+            Self::Delete => "delete",
+            Self::Anonymize => "anonymize",
+        };
+
+        <String as ToSql<VarChar, Pg>>::to_sql(&value.to_owned(), out)?;
+
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<VarChar, Pg> for ErasureMode {
+    fn from_sql(
+        bytes: Option<&<Pg as diesel::backend::Backend>::RawValue>,
+    ) -> diesel::deserialize::Result<Self> {
+        let bytes = bytes.ok_or_else(|| anyhow!("no bytes given"))?;
+
+        match bytes {
+            b"delete" => Ok(ErasureMode::Delete),
+            b"anonymize" => Ok(ErasureMode::Anonymize),
+            _ => Err(anyhow!("Bytes given doesn't match ErasureMode type"))?,
+        }
+    }
+}
+
+/// Audit record of erased user, it keeps only twitch user id of who was erased
+#[derive(Queryable, Serialize, Debug, Clone)]
+pub struct UserErasure {
+    pub id: i32,
+    pub uuid: Uuid,
+    pub twitch_user_id: String,
+    pub mode: ErasureMode,
+    /// who asked for the erasure, e.g. the user themselves or support ticket
+    pub requested_by: String,
+    pub reason: Option<String>,
+    /// number of deleted or anonymized messages
+    pub messages: i64,
+    pub old_names: i64,
+    pub erased_at: DateTime<Utc>,
+    /// whether copies of the user in retention and cold archive files were removed, erasure
+    /// itself doesn't touch them
+    pub archives_processed: bool,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[table_name = "user_erasures"]
+pub struct NewUserErasure {
+    pub twitch_user_id: String,
+    pub mode: ErasureMode,
+    pub requested_by: String,
+    pub reason: Option<String>,
+    pub messages: i64,
+    pub old_names: i64,
+    pub erased_at: DateTime<Utc>,
+    pub archives_processed: bool,
+}
@@ -1,23 +1,90 @@
+table! {
+    api_keys (id) {
+        id -> Int4,
+        uuid -> Uuid,
+        name -> Varchar,
+        key_hash -> Varchar,
+        scopes -> Array<Varchar>,
+        created_at -> Timestamptz,
+        revoked_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    channel_leases (id) {
+        id -> Int4,
+        channel_id -> Int4,
+        instance_id -> Varchar,
+        expires_at -> Timestamptz,
+    }
+}
+
 table! {
     channels (id) {
         id -> Int4,
         uuid -> Uuid,
         twitch_channel_id -> Varchar,
         channel_name -> Varchar,
+        collecting_since -> Nullable<Timestamptz>,
+        last_collected_at -> Nullable<Timestamptz>,
+        stored_events -> Array<Varchar>,
+        store_text -> Bool,
+        retention_days -> Nullable<Int4>,
+        paused -> Bool,
     }
 }
 
 table! {
-    messages (id) {
+    channels_old_names (id) {
+        id -> Int4,
+        channel_id -> Int4,
+        channel_name -> Varchar,
+        first_time_with_new_name -> Timestamptz,
+    }
+}
+
+table! {
+    collection_gaps (id) {
+        id -> Int4,
+        uuid -> Uuid,
+        channel_id -> Int4,
+        started_at -> Timestamptz,
+        ended_at -> Timestamptz,
+        reason -> Varchar,
+    }
+}
+
+table! {
+    collector_instances (id) {
+        id -> Int4,
+        instance_id -> Varchar,
+        last_seen_at -> Timestamptz,
+    }
+}
+
+table! {
+    irc_tokens (id) {
+        id -> Int4,
+        login -> Varchar,
+        access_token -> Varchar,
+        refresh_token -> Varchar,
+        created_at -> Timestamptz,
+        expires_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    messages (id, send_time) {
         id -> Int8,
         uuid -> Uuid,
-        msg -> Varchar,
+        msg -> Nullable<Varchar>,
         msg_type -> Varchar,
-        user_id -> Int4,
+        user_id -> Nullable<Int4>,
         channel_id -> Int4,
         resub_id -> Nullable<Int4>,
         send_time -> Timestamptz,
         bits -> Nullable<Int8>,
+        stream_session_id -> Nullable<Int4>,
     }
 }
 
@@ -30,6 +97,61 @@ table! {
     }
 }
 
+table! {
+    room_state_changes (id) {
+        id -> Int4,
+        uuid -> Uuid,
+        channel_id -> Int4,
+        change_time -> Timestamptz,
+        emote_only -> Nullable<Bool>,
+        followers_only_minutes -> Nullable<Int4>,
+        r9k -> Nullable<Bool>,
+        slow_mode_seconds -> Nullable<Int4>,
+        subscribers_only -> Nullable<Bool>,
+    }
+}
+
+table! {
+    stream_session_changes (id) {
+        id -> Int4,
+        uuid -> Uuid,
+        stream_session_id -> Int4,
+        change_time -> Timestamptz,
+        title -> Varchar,
+        category_id -> Varchar,
+        category_name -> Varchar,
+    }
+}
+
+table! {
+    stream_sessions (id) {
+        id -> Int4,
+        uuid -> Uuid,
+        channel_id -> Int4,
+        twitch_stream_id -> Varchar,
+        title -> Varchar,
+        category_id -> Varchar,
+        category_name -> Varchar,
+        started_at -> Timestamptz,
+        ended_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    user_erasures (id) {
+        id -> Int4,
+        uuid -> Uuid,
+        twitch_user_id -> Varchar,
+        mode -> Varchar,
+        requested_by -> Varchar,
+        reason -> Nullable<Varchar>,
+        messages -> Int8,
+        old_names -> Int8,
+        erased_at -> Timestamptz,
+        archives_processed -> Bool,
+    }
+}
+
 table! {
     users (id) {
         id -> Int4,
@@ -48,15 +170,32 @@ table! {
     }
 }
 
+joinable!(channel_leases -> channels (channel_id));
+joinable!(channels_old_names -> channels (channel_id));
+joinable!(collection_gaps -> channels (channel_id));
 joinable!(messages -> channels (channel_id));
 joinable!(messages -> resubs (resub_id));
+joinable!(messages -> stream_sessions (stream_session_id));
 joinable!(messages -> users (user_id));
+joinable!(room_state_changes -> channels (channel_id));
+joinable!(stream_session_changes -> stream_sessions (stream_session_id));
+joinable!(stream_sessions -> channels (channel_id));
 joinable!(users_old_names -> users (user_id));
 
 allow_tables_to_appear_in_same_query!(
+    api_keys,
+    channel_leases,
     channels,
+    channels_old_names,
+    collection_gaps,
+    collector_instances,
+    irc_tokens,
     messages,
     resubs,
+    room_state_changes,
+    stream_session_changes,
+    stream_sessions,
+    user_erasures,
     users,
     users_old_names,
 );
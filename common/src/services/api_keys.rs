@@ -0,0 +1,81 @@
+use chrono::Utc;
+use diesel::{prelude::*, PgConnection};
+use error_stack::{IntoReport, Result, ResultExt};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    models::api_key::{ApiKey, ApiScope, NewApiKey},
+    schema::api_keys,
+};
+
+/// makes keys recognizable, e.g. by secret scanners
+const KEY_PREFIX: &str = "tch_";
+
+/// Creates api key, returns it together with the key itself which isn't stored anywhere
+pub fn create(
+    db_conn: &PgConnection,
+    name: &str,
+    scopes: Vec<ApiScope>,
+) -> Result<(ApiKey, String), diesel::result::Error> {
+    let key = format!("{KEY_PREFIX}{}", hex(&rand::thread_rng().gen::<[u8; 32]>()));
+
+    let api_key = diesel::insert_into(api_keys::table)
+        .values(NewApiKey {
+            name: name.to_owned(),
+            key_hash: hash(&key),
+            scopes,
+            created_at: Utc::now(),
+        })
+        .get_result(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| format!("database error: couldn't create api key {name}"))?;
+
+    Ok((api_key, key))
+}
+
+/// Api key that isn't revoked
+pub fn get_by_key(
+    db_conn: &PgConnection,
+    key: &str,
+) -> Result<Option<ApiKey>, diesel::result::Error> {
+    api_keys::table
+        .filter(api_keys::key_hash.eq(hash(key)))
+        .filter(api_keys::revoked_at.is_null())
+        .first(db_conn)
+        .optional()
+        .into_report()
+        .attach_printable("database error: couldn't get api key")
+}
+
+pub fn get_all(db_conn: &PgConnection) -> Result<Vec<ApiKey>, diesel::result::Error> {
+    api_keys::table
+        .order(api_keys::id.asc())
+        .load(db_conn)
+        .into_report()
+        .attach_printable("database error: couldn't get api keys")
+}
+
+/// Returns false when there's no such key or it's already revoked
+pub fn revoke(db_conn: &PgConnection, uuid: Uuid) -> Result<bool, diesel::result::Error> {
+    diesel::update(
+        api_keys::table
+            .filter(api_keys::uuid.eq(uuid))
+            .filter(api_keys::revoked_at.is_null()),
+    )
+    .set(api_keys::revoked_at.eq(Utc::now()))
+    .execute(db_conn)
+    .map(|updated| updated > 0)
+    .into_report()
+    .attach_printable_lazy(|| format!("database error: couldn't revoke api key {uuid}"))
+}
+
+/// Keys are long and random, so plain sha256 is enough
+fn hash(key: &str) -> String {
+    hex(&Sha256::digest(key.as_bytes()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
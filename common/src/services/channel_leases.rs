@@ -0,0 +1,107 @@
+use chrono::{DateTime, Utc};
+use diesel::{prelude::*, PgConnection};
+use error_stack::{IntoReport, Result, ResultExt};
+
+use crate::{models::channel_lease::NewChannelLease, schema::channel_leases};
+
+/// Ids of channels with lease that didn't expire yet, no matter which instance holds it
+pub fn get_taken_channel_ids(
+    db_conn: &PgConnection,
+    now: DateTime<Utc>,
+) -> Result<Vec<i32>, diesel::result::Error> {
+    channel_leases::table
+        .filter(channel_leases::expires_at.gt(now))
+        .select(channel_leases::channel_id)
+        .load(db_conn)
+        .into_report()
+        .attach_printable("database error: couldn't get taken channel leases")
+}
+
+/// Takes channel for `instance_id` if nobody holds it or previous lease expired.
+/// Returns false if some other instance was faster.
+pub fn claim(
+    db_conn: &PgConnection,
+    channel_id: i32,
+    instance_id: &str,
+    expires_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<bool, diesel::result::Error> {
+    // row lock makes sure only one instance takes over expired lease
+    let taken_over = diesel::update(channel_leases::table)
+        .filter(channel_leases::channel_id.eq(channel_id))
+        .filter(channel_leases::expires_at.le(now))
+        .set((
+            channel_leases::instance_id.eq(instance_id),
+            channel_leases::expires_at.eq(expires_at),
+        ))
+        .execute(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't take over lease of channel_id: {channel_id}")
+        })?;
+
+    if taken_over > 0 {
+        return Ok(true);
+    }
+
+    let inserted = diesel::insert_into(channel_leases::table)
+        .values(NewChannelLease {
+            channel_id,
+            instance_id: instance_id.to_owned(),
+            expires_at,
+        })
+        .on_conflict(channel_leases::channel_id)
+        .do_nothing()
+        .execute(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't create lease of channel_id: {channel_id}")
+        })?;
+
+    Ok(inserted > 0)
+}
+
+/// Extends every lease of `instance_id`, returns ids of channels it still holds
+pub fn renew(
+    db_conn: &PgConnection,
+    instance_id: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<Vec<i32>, diesel::result::Error> {
+    diesel::update(channel_leases::table)
+        .filter(channel_leases::instance_id.eq(instance_id))
+        .set(channel_leases::expires_at.eq(expires_at))
+        .returning(channel_leases::channel_id)
+        .get_results(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't renew leases of instance {instance_id}")
+        })
+}
+
+pub fn release(
+    db_conn: &PgConnection,
+    instance_id: &str,
+    channel_ids: &[i32],
+) -> Result<usize, diesel::result::Error> {
+    diesel::delete(channel_leases::table)
+        .filter(channel_leases::instance_id.eq(instance_id))
+        .filter(channel_leases::channel_id.eq_any(channel_ids))
+        .execute(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't release leases of instance {instance_id}")
+        })
+}
+
+pub fn release_all(
+    db_conn: &PgConnection,
+    instance_id: &str,
+) -> Result<usize, diesel::result::Error> {
+    diesel::delete(channel_leases::table)
+        .filter(channel_leases::instance_id.eq(instance_id))
+        .execute(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't release leases of instance {instance_id}")
+        })
+}
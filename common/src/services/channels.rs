@@ -1,11 +1,35 @@
+use chrono::{DateTime, Utc};
 use diesel::{prelude::*, PgConnection};
-use error_stack::{IntoReport, Result, ResultExt};
+use error_stack::{IntoReport, Report, Result, ResultExt};
 
 use crate::{
-    models::channel::{Channel, NewChannel},
-    schema::channels,
+    models::channel::{Channel, ChannelPolicy, NewChannel},
+    schema::{channels, channels_old_names},
 };
 
+/// Every tracked channel, ordered by name
+pub fn get_channels(db_conn: &PgConnection) -> Result<Vec<Channel>, diesel::result::Error> {
+    channels::table
+        .order(channels::channel_name.asc())
+        .load(db_conn)
+        .into_report()
+        .attach_printable("database error: couldn't get channels")
+}
+
+pub fn get_channel_by_id(
+    db_conn: &PgConnection,
+    channel_id: i32,
+) -> Result<Option<Channel>, diesel::result::Error> {
+    channels::table
+        .find(channel_id)
+        .first(db_conn)
+        .optional()
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't get channel with id: {channel_id}")
+        })
+}
+
 pub fn get_channel_by_twitch_id(
     db_conn: &PgConnection,
     twitch_channel_id: &str,
@@ -25,6 +49,48 @@ pub fn get_channel_by_twitch_id(
         })
 }
 
+pub fn get_channel_by_name(
+    db_conn: &PgConnection,
+    channel_name: &str,
+) -> Result<Option<Channel>, diesel::result::Error> {
+    log::trace!("getting channel by name: {:?}", channel_name);
+
+    channels::table
+        .filter(channels::channel_name.eq(channel_name))
+        .first(db_conn)
+        .optional()
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!(
+                "database error: couldn't get channel with name {}",
+                channel_name
+            )
+        })
+}
+
+/// Finds channel which used to be called `channel_name`, newest rename wins
+pub fn get_channel_by_old_name(
+    db_conn: &PgConnection,
+    channel_name: &str,
+) -> Result<Option<Channel>, diesel::result::Error> {
+    log::trace!("getting channel by old name: {:?}", channel_name);
+
+    channels::table
+        .inner_join(channels_old_names::table)
+        .filter(channels_old_names::channel_name.eq(channel_name))
+        .order(channels_old_names::first_time_with_new_name.desc())
+        .select(channels::all_columns)
+        .first(db_conn)
+        .optional()
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!(
+                "database error: couldn't get channel with old name {}",
+                channel_name
+            )
+        })
+}
+
 pub fn create_channel_if_not_exists(
     db_conn: &PgConnection,
     twitch_channel_id: String,
@@ -38,7 +104,7 @@ pub fn create_channel_if_not_exists(
     let channel = get_channel_by_twitch_id(db_conn, &twitch_channel_id)?;
 
     match channel {
-        Some(channel) => Ok(channel),
+        Some(channel) => check_and_fix_channel_name(db_conn, channel, &channel_name, Utc::now()),
         None => Ok(create_channel(db_conn, twitch_channel_id, channel_name)?),
     }
 }
@@ -61,3 +127,103 @@ pub fn create_channel(
             format!("database error: couldn't create channel: {channel_name}")
         })
 }
+
+/// Records old name and renames channel if `channel_name` differs from the stored one
+pub fn check_and_fix_channel_name(
+    db_conn: &PgConnection,
+    channel: Channel,
+    channel_name: &str,
+    timestamp: DateTime<Utc>,
+) -> Result<Channel, diesel::result::Error> {
+    if channel.channel_name == channel_name {
+        return Ok(channel);
+    }
+
+    log::info!(
+        "channel {} was renamed to {}",
+        channel.channel_name,
+        channel_name
+    );
+
+    // old name is recorded only if the rename goes through, so it isn't recorded twice
+    db_conn
+        .transaction::<_, Report<diesel::result::Error>, _>(|| {
+            super::channels_old_names::create(
+                db_conn,
+                channel.id,
+                channel.channel_name,
+                timestamp,
+            )?;
+
+            diesel::update(channels::table)
+                .filter(channels::id.eq(channel.id))
+                .set(channels::channel_name.eq(channel_name))
+                .get_result(db_conn)
+                .into_report()
+        })
+        .attach_printable_lazy(|| {
+            format!(
+                "database error: couldn't rename channel with id {} to {channel_name}",
+                channel.id
+            )
+        })
+}
+
+/// Moves `last_collected_at` of channels to `timestamp`, `collecting_since` is set on first call
+pub fn mark_collected(
+    db_conn: &PgConnection,
+    channel_ids: &[i32],
+    timestamp: DateTime<Utc>,
+) -> Result<usize, diesel::result::Error> {
+    diesel::update(channels::table)
+        .filter(channels::id.eq_any(channel_ids))
+        .filter(channels::collecting_since.is_null())
+        .set(channels::collecting_since.eq(timestamp))
+        .execute(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't set collecting_since of channels: {channel_ids:?}")
+        })?;
+
+    diesel::update(channels::table)
+        .filter(channels::id.eq_any(channel_ids))
+        .set(channels::last_collected_at.eq(timestamp))
+        .execute(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't set last_collected_at of channels: {channel_ids:?}")
+        })
+}
+
+/// Stores `policy` of channel if it differs from the stored one
+pub fn set_policy(
+    db_conn: &PgConnection,
+    channel: Channel,
+    policy: &ChannelPolicy,
+) -> Result<Channel, diesel::result::Error> {
+    if channel.policy() == *policy {
+        return Ok(channel);
+    }
+
+    log::info!(
+        "updating collection policy of channel {}",
+        channel.channel_name
+    );
+
+    diesel::update(channels::table)
+        .filter(channels::id.eq(channel.id))
+        .set((
+            channels::stored_events.eq(&policy.stored_events),
+            channels::store_text.eq(policy.store_text),
+            channels::retention_days.eq(policy.retention_days),
+            channels::paused.eq(policy.paused),
+        ))
+        .get_result(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!(
+                "database error: couldn't set policy of channel {}",
+                channel.channel_name
+            )
+        })
+}
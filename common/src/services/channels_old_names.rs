@@ -0,0 +1,27 @@
+use chrono::{DateTime, Utc};
+use diesel::{prelude::*, PgConnection};
+use error_stack::{IntoReport, ResultExt};
+
+use crate::{models::channel_old_name::NewChannelOldName, schema::channels_old_names};
+
+pub fn create(
+    db_conn: &PgConnection,
+    channel_id: i32,
+    old_name: String,
+    first_time_with_new_name: DateTime<Utc>,
+) -> error_stack::Result<usize, diesel::result::Error> {
+    diesel::insert_into(channels_old_names::table)
+        .values(NewChannelOldName {
+            channel_id,
+            channel_name: old_name.clone(),
+            first_time_with_new_name,
+        })
+        .execute(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!(
+                "values: channel_id: {}, channel_name: {}",
+                channel_id, old_name
+            )
+        })
+}
@@ -0,0 +1,225 @@
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Utc};
+use diesel::{
+    prelude::*,
+    sql_types::{BigInt, Nullable, Text},
+    PgConnection,
+};
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use uuid::Uuid;
+
+use crate::schema::{channels, messages, resubs, user_erasures, users};
+
+use super::resubs as resubs_service;
+
+/// Message joined with its user, channel and resub, one row of the archive
+#[derive(Queryable, Debug, Clone)]
+pub struct ArchivedMessage {
+    pub id: i64,
+    pub uuid: Uuid,
+    pub send_time: DateTime<Utc>,
+    pub msg_type: String,
+    pub msg: Option<String>,
+    pub bits: Option<i64>,
+    pub stream_session_id: Option<i32>,
+    pub twitch_channel_id: String,
+    pub channel_name: String,
+    /// none for messages of erased users
+    pub twitch_user_id: Option<String>,
+    pub username: Option<String>,
+    pub resub_cumulative_month: Option<i16>,
+    pub resub_tier: Option<i16>,
+}
+
+/// What monthly partition of messages holds
+#[derive(QueryableByName, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartitionContents {
+    #[sql_type = "BigInt"]
+    pub messages: i64,
+    /// none when partition is empty
+    #[sql_type = "Nullable<BigInt>"]
+    pub last_id: Option<i64>,
+}
+
+pub enum DropOutcome {
+    /// partition is gone together with resubs only its messages had
+    Dropped { resubs: usize },
+    /// messages were added or deleted since export, partition holds this instead
+    Changed(PartitionContents),
+    /// user was erased since export started, archive might still have their messages
+    UserErased,
+}
+
+#[derive(QueryableByName)]
+struct Partition {
+    #[sql_type = "Text"]
+    name: String,
+}
+
+/// First days of months that have partition of messages, oldest first
+pub fn get_partition_months(
+    db_conn: &PgConnection,
+) -> Result<Vec<NaiveDate>, diesel::result::Error> {
+    let partitions = diesel::sql_query(
+        "SELECT child.relname::TEXT AS name FROM pg_inherits \
+         JOIN pg_class parent ON parent.oid = pg_inherits.inhparent \
+         JOIN pg_class child ON child.oid = pg_inherits.inhrelid \
+         WHERE parent.relname = 'messages'",
+    )
+    .load::<Partition>(db_conn)
+    .into_report()
+    .attach_printable("database error: couldn't get partitions of messages")?;
+
+    let mut months: Vec<NaiveDate> = partitions
+        .iter()
+        .filter_map(|partition| {
+            NaiveDate::parse_from_str(&format!("{}_01", partition.name), "messages_%Y_%m_%d").ok()
+        })
+        .collect();
+    months.sort();
+
+    Ok(months)
+}
+
+/// Messages of month starting at `month` with id greater than `after_id`, at most `limit` of them
+pub fn get_partition_messages(
+    db_conn: &PgConnection,
+    month: NaiveDate,
+    after_id: i64,
+    limit: i64,
+) -> Result<Vec<ArchivedMessage>, diesel::result::Error> {
+    let (from, to) = month_bounds(month);
+
+    messages::table
+        .left_join(users::table)
+        .inner_join(channels::table)
+        .left_join(resubs::table)
+        .select((
+            messages::id,
+            messages::uuid,
+            messages::send_time,
+            messages::msg_type,
+            messages::msg,
+            messages::bits,
+            messages::stream_session_id,
+            channels::twitch_channel_id,
+            channels::channel_name,
+            users::twitch_user_id.nullable(),
+            users::username.nullable(),
+            resubs::cumulative_month.nullable(),
+            resubs::tier.nullable(),
+        ))
+        .filter(messages::send_time.ge(from))
+        .filter(messages::send_time.lt(to))
+        .filter(messages::id.gt(after_id))
+        .order(messages::id.asc())
+        .limit(limit)
+        .load(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!(
+                "database error: couldn't get messages of {}",
+                month.format("%Y-%m")
+            )
+        })
+}
+
+/// Drops partition of month starting at `month` together with resubs only its messages had, if it
+/// still holds `exported` and no user was erased since `export_started`. Partition is locked
+/// first, so nothing can change it between the check and the drop.
+pub fn drop_partition(
+    db_conn: &PgConnection,
+    month: NaiveDate,
+    exported: PartitionContents,
+    export_started: DateTime<Utc>,
+) -> Result<DropOutcome, diesel::result::Error> {
+    // name comes from formatted date, so it's safe to put into queries
+    let partition = month.format("messages_%Y_%m").to_string();
+
+    db_conn
+        .transaction::<_, Report<diesel::result::Error>, _>(|| {
+            diesel::sql_query(format!("LOCK TABLE {partition} IN ACCESS EXCLUSIVE MODE"))
+                .execute(db_conn)
+                .into_report()?;
+
+            let contents = diesel::sql_query(format!(
+                "SELECT count(*) AS messages, max(id) AS last_id FROM {partition}"
+            ))
+            .get_result::<PartitionContents>(db_conn)
+            .into_report()?;
+            if contents != exported {
+                return Ok(DropOutcome::Changed(contents));
+            }
+
+            let erasures = user_erasures::table
+                .filter(user_erasures::erased_at.ge(export_started))
+                .count()
+                .get_result::<i64>(db_conn)
+                .into_report()?;
+            if erasures > 0 {
+                return Ok(DropOutcome::UserErased);
+            }
+
+            diesel::sql_query(format!("DROP TABLE {partition}"))
+                .execute(db_conn)
+                .into_report()?;
+
+            let resubs = resubs_service::delete_orphaned(db_conn)?;
+
+            Ok(DropOutcome::Dropped { resubs })
+        })
+        .attach_printable_lazy(|| {
+            format!(
+                "database error: couldn't drop partition of {}",
+                month.format("%Y-%m")
+            )
+        })
+}
+
+/// Start of month starting at `month` and start of the next one, in UTC like partition bounds
+pub fn month_bounds(month: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+    let next_month = match month.month() {
+        12 => NaiveDate::from_ymd_opt(month.year() + 1, 1, 1),
+        next => NaiveDate::from_ymd_opt(month.year(), next + 1, 1),
+    }
+    .expect("first day of month is valid date");
+
+    let start_of = |date: NaiveDate| {
+        Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).expect("midnight is valid time"))
+    };
+
+    (start_of(month), start_of(next_month))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn midnight(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        Utc.from_utc_datetime(
+            &NaiveDate::from_ymd_opt(year, month, day)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn month_bounds_cover_whole_month() {
+        let month = NaiveDate::from_ymd_opt(2022, 2, 1).unwrap();
+
+        assert_eq!(
+            month_bounds(month),
+            (midnight(2022, 2, 1), midnight(2022, 3, 1))
+        );
+    }
+
+    #[test]
+    fn month_bounds_of_december_end_in_next_year() {
+        let month = NaiveDate::from_ymd_opt(2022, 12, 1).unwrap();
+
+        assert_eq!(
+            month_bounds(month),
+            (midnight(2022, 12, 1), midnight(2023, 1, 1))
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use chrono::{DateTime, Utc};
+use diesel::{prelude::*, PgConnection};
+use error_stack::{IntoReport, Result, ResultExt};
+
+use crate::{
+    models::{
+        channel::Channel,
+        collection_gap::{CollectionGap, Coverage, GapReason, NewCollectionGap, TimeRange},
+    },
+    schema::collection_gaps,
+};
+
+use super::channels;
+
+pub fn create(
+    db_conn: &PgConnection,
+    new_collection_gap: NewCollectionGap,
+) -> Result<usize, diesel::result::Error> {
+    let channel_id = new_collection_gap.channel_id;
+
+    diesel::insert_into(collection_gaps::table)
+        .values(new_collection_gap)
+        .execute(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't insert collection gap for channel_id: {channel_id}")
+        })
+}
+
+pub fn get_by_channel_id(
+    db_conn: &PgConnection,
+    channel_id: i32,
+) -> Result<Vec<CollectionGap>, diesel::result::Error> {
+    collection_gaps::table
+        .filter(collection_gaps::channel_id.eq(channel_id))
+        .order(collection_gaps::started_at.asc())
+        .load(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't get collection gaps of channel_id: {channel_id}")
+        })
+}
+
+/// Called when collector starts recording channel again. Everything since its
+/// `last_collected_at` is recorded as a gap.
+pub fn resume_collecting(
+    db_conn: &PgConnection,
+    channel_id: i32,
+    reason: GapReason,
+    timestamp: DateTime<Utc>,
+) -> Result<(), diesel::result::Error> {
+    let last_collected_at = channels::get_channel_by_id(db_conn, channel_id)?
+        .and_then(|channel| channel.last_collected_at);
+
+    if let Some(last_collected_at) = last_collected_at {
+        if last_collected_at < timestamp {
+            create(
+                db_conn,
+                NewCollectionGap {
+                    channel_id,
+                    started_at: last_collected_at,
+                    ended_at: timestamp,
+                    reason,
+                },
+            )?;
+        }
+    }
+
+    channels::mark_collected(db_conn, &[channel_id], timestamp)?;
+
+    Ok(())
+}
+
+/// Splits time between `collecting_since` and `last_collected_at` of `channel` into covered
+/// ranges and gaps
+pub fn get_coverage(
+    db_conn: &PgConnection,
+    channel: &Channel,
+) -> Result<Coverage, diesel::result::Error> {
+    let gaps = get_by_channel_id(db_conn, channel.id)?;
+
+    let (Some(start), Some(end)) = (channel.collecting_since, channel.last_collected_at) else {
+        return Ok(Coverage {
+            covered: vec![],
+            gaps,
+        });
+    };
+
+    let mut covered = vec![];
+    let mut cursor = start;
+
+    for gap in &gaps {
+        if gap.started_at > cursor {
+            covered.push(TimeRange {
+                start: cursor,
+                end: gap.started_at.min(end),
+            });
+        }
+
+        cursor = cursor.max(gap.ended_at);
+    }
+
+    if cursor < end {
+        covered.push(TimeRange { start: cursor, end });
+    }
+
+    Ok(Coverage { covered, gaps })
+}
@@ -0,0 +1,51 @@
+use chrono::{DateTime, Utc};
+use diesel::{prelude::*, PgConnection};
+use error_stack::{IntoReport, Result, ResultExt};
+
+use crate::{models::collector_instance::NewCollectorInstance, schema::collector_instances};
+
+/// Records that instance is alive at `timestamp`
+pub fn heartbeat(
+    db_conn: &PgConnection,
+    instance_id: &str,
+    timestamp: DateTime<Utc>,
+) -> Result<usize, diesel::result::Error> {
+    let new_collector_instance = NewCollectorInstance {
+        instance_id: instance_id.to_owned(),
+        last_seen_at: timestamp,
+    };
+
+    diesel::insert_into(collector_instances::table)
+        .values(&new_collector_instance)
+        .on_conflict(collector_instances::instance_id)
+        .do_update()
+        .set(&new_collector_instance)
+        .execute(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't update collector instance {instance_id}")
+        })
+}
+
+/// Number of instances seen since `since`
+pub fn count_alive(
+    db_conn: &PgConnection,
+    since: DateTime<Utc>,
+) -> Result<i64, diesel::result::Error> {
+    collector_instances::table
+        .filter(collector_instances::last_seen_at.ge(since))
+        .count()
+        .get_result(db_conn)
+        .into_report()
+        .attach_printable("database error: couldn't count alive collector instances")
+}
+
+pub fn remove(db_conn: &PgConnection, instance_id: &str) -> Result<usize, diesel::result::Error> {
+    diesel::delete(collector_instances::table)
+        .filter(collector_instances::instance_id.eq(instance_id))
+        .execute(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't remove collector instance {instance_id}")
+        })
+}
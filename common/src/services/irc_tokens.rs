@@ -0,0 +1,36 @@
+use diesel::{prelude::*, PgConnection};
+use error_stack::{IntoReport, Result, ResultExt};
+
+use crate::{
+    models::irc_token::{IrcToken, NewIrcToken},
+    schema::irc_tokens,
+};
+
+pub fn get_by_login(
+    db_conn: &PgConnection,
+    login: &str,
+) -> Result<Option<IrcToken>, diesel::result::Error> {
+    irc_tokens::table
+        .filter(irc_tokens::login.eq(login))
+        .first(db_conn)
+        .optional()
+        .into_report()
+        .attach_printable_lazy(|| format!("database error: couldn't get irc token of {login}"))
+}
+
+/// Inserts token or replaces the one already stored for the same login
+pub fn upsert(
+    db_conn: &PgConnection,
+    new_irc_token: NewIrcToken,
+) -> Result<usize, diesel::result::Error> {
+    let login = new_irc_token.login.clone();
+
+    diesel::insert_into(irc_tokens::table)
+        .values(&new_irc_token)
+        .on_conflict(irc_tokens::login)
+        .do_update()
+        .set(&new_irc_token)
+        .execute(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| format!("database error: couldn't save irc token of {login}"))
+}
@@ -1,24 +1,25 @@
-use chrono::{DateTime, Utc};
-use diesel::{prelude::*, PgConnection};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, Utc};
+use diesel::{prelude::*, sql_types::Timestamp, PgConnection};
 use error_stack::{IntoReport, Result, ResultExt};
 
 use crate::{
     models::{
-        message::{MsgType, NewMessage},
-        resub::NewResub,
-        user::NewUser,
+        message::{Message, MsgType, NewMessage},
+        resub::{NewResub, Resub},
+        stream_session::StreamSession,
+        user::{NewUser, User},
     },
-    schema::messages,
+    schema::{messages, resubs as resubs_table, users as users_table},
 };
 
 use super::{
-    resubs,
+    resubs, stream_sessions,
     users::{self, create_user},
 };
 
 pub fn create_message(
     db_conn: &PgConnection,
-    msg: String,
+    msg: Option<String>,
     msg_type: MsgType,
     channel_id: i32,
     send_time: DateTime<Utc>,
@@ -28,47 +29,227 @@ pub fn create_message(
     twitch_username: String,
     _twitch_displayname: String,
 ) -> Result<usize, diesel::result::Error> {
-    let user = users::get_user_by_user_id(&twitch_user_id, db_conn)?
-        .map_or_else(
-            || {
-                let new_user = NewUser {
-                    username: twitch_username.clone(),
-                    twitch_user_id,
-                };
-
-                create_user(new_user, db_conn)
-            },
-            |v| Ok(v),
-        )
-        .attach_printable_lazy(|| {
-            format!("couldn't create message for user because of db error while getting user")
-        })?;
+    // resub is committed together with message pointing to it, otherwise pruning of orphaned
+    // resubs running in between would delete it and insert of message would fail
+    db_conn.transaction(|| {
+        let user = users::get_user_by_user_id(&twitch_user_id, db_conn)?
+            .map_or_else(
+                || {
+                    let new_user = NewUser {
+                        username: twitch_username.clone(),
+                        twitch_user_id,
+                    };
+
+                    create_user(new_user, db_conn)
+                },
+                |v| Ok(v),
+            )
+            .attach_printable_lazy(|| {
+                format!("couldn't create message for user because of db error while getting user")
+            })?;
+
+        let user_id = user.id;
+
+        users::check_and_fix_username(db_conn, user, &twitch_username, send_time)?;
+
+        let new_resub = new_resub.map(|r| resubs::create_resub_return(db_conn, r));
+
+        let resub_id = match new_resub {
+            Some(r) => Some(r?),
+            None => None,
+        }.map(|r| r.id);
+
+        let stream_session_id =
+            stream_sessions::get_session_at(db_conn, channel_id, send_time)?.map(|s| s.id);
+
+        // message.user_id = user.id;
+        let message = NewMessage {
+            msg,
+            msg_type,
+            user_id: Some(user_id),
+            channel_id,
+            send_time,
+            bits,
+            resub_id,
+            stream_session_id,
+        };
+
+        diesel::insert_into(messages::table)
+            .values(message)
+            .execute(db_conn)
+            .into_report()
+            .attach_printable("database error: couldn't insert message")
+    })
+}
 
-    let user_id = user.id;
+/// Message with its user (none for anonymized messages of erased users) and resub
+pub type MessageWithUser = (Message, Option<User>, Option<Resub>);
 
-    users::check_and_fix_username(db_conn, user, &twitch_username, send_time)?;
+/// Messages of stream session with id greater than `after_id`, at most `limit` of them,
+/// ordered by id which follows the order they were received in
+pub fn get_by_stream_session(
+    db_conn: &PgConnection,
+    stream_session: &StreamSession,
+    after_id: i64,
+    limit: i64,
+) -> Result<Vec<MessageWithUser>, diesel::result::Error> {
+    let stream_session_id = stream_session.id;
 
-    let new_resub = new_resub.map(|r| resubs::create_resub_return(db_conn, r));
+    // bounds on send_time let postgres skip partitions of other months
+    let mut query = messages::table
+        .left_join(users_table::table)
+        .left_join(resubs_table::table)
+        .filter(messages::stream_session_id.eq(stream_session_id))
+        .filter(messages::send_time.ge(stream_session.started_at))
+        .filter(messages::id.gt(after_id))
+        .into_boxed();
 
-    let resub_id = match new_resub {
-        Some(r) => Some(r?),
-        None => None,
-    }.map(|r| r.id);
+    if let Some(ended_at) = stream_session.ended_at {
+        query = query.filter(messages::send_time.le(ended_at));
+    }
 
-    // message.user_id = user.id;
-    let message = NewMessage {
-        msg,
-        msg_type,
-        user_id,
-        channel_id,
-        send_time,
-        bits,
-        resub_id,
-    };
+    query
+        .order(messages::id.asc())
+        .limit(limit)
+        .load(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't get messages of stream session with id: {stream_session_id}")
+        })
+}
 
-    diesel::insert_into(messages::table)
-        .values(message)
+/// Messages of channel sent in `[from, to)` with id greater than `after_id`, at most `limit`
+/// of them, ordered by id
+pub fn get_by_channel_id(
+    db_conn: &PgConnection,
+    channel_id: i32,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    after_id: i64,
+    limit: i64,
+) -> Result<Vec<MessageWithUser>, diesel::result::Error> {
+    messages::table
+        .left_join(users_table::table)
+        .left_join(resubs_table::table)
+        .filter(messages::channel_id.eq(channel_id))
+        .filter(messages::send_time.ge(from))
+        .filter(messages::send_time.lt(to))
+        .filter(messages::id.gt(after_id))
+        .order(messages::id.asc())
+        .limit(limit)
+        .load(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't get messages of channel with id: {channel_id} from {from} to {to}")
+        })
+}
+
+/// Links messages sent since `started_at` that weren't linked to any stream session yet or were
+/// linked to `previous_session_id`. Needed because stream going live is noticed only on the next
+/// poll.
+pub fn link_to_stream_session(
+    db_conn: &PgConnection,
+    channel_id: i32,
+    stream_session_id: i32,
+    started_at: DateTime<Utc>,
+    previous_session_id: Option<i32>,
+) -> Result<usize, diesel::result::Error> {
+    diesel::update(messages::table)
+        .filter(messages::channel_id.eq(channel_id))
+        .filter(
+            messages::stream_session_id
+                .is_null()
+                .or(messages::stream_session_id.eq(previous_session_id)),
+        )
+        .filter(messages::send_time.ge(started_at))
+        .set(messages::stream_session_id.eq(stream_session_id))
         .execute(db_conn)
         .into_report()
-        .attach_printable("database error: couldn't insert message")
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't link messages to stream session with id: {stream_session_id}")
+        })
+}
+
+/// Makes sure monthly partitions of `messages` exist for month of `from` and `months_ahead`
+/// months after it
+pub fn create_partitions(
+    db_conn: &PgConnection,
+    from: DateTime<Utc>,
+    months_ahead: u32,
+) -> Result<(), diesel::result::Error> {
+    for month_start in partition_months(from, months_ahead) {
+        diesel::sql_query("SELECT create_messages_partition($1)")
+            .bind::<Timestamp, _>(month_start)
+            .execute(db_conn)
+            .into_report()
+            .attach_printable_lazy(|| {
+                format!(
+                    "database error: couldn't create messages partition for {}",
+                    month_start.format("%Y-%m")
+                )
+            })?;
+    }
+
+    Ok(())
+}
+
+/// Starts of month containing `from` and `months_ahead` months after it
+fn partition_months(from: DateTime<Utc>, months_ahead: u32) -> Vec<NaiveDateTime> {
+    let (mut year, mut month) = (from.year(), from.month());
+    let mut months = Vec::with_capacity(months_ahead as usize + 1);
+
+    for _ in 0..=months_ahead {
+        let month_start = NaiveDate::from_ymd_opt(year, month, 1)
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .expect("first day of month is valid date");
+        months.push(month_start);
+
+        (year, month) = match month {
+            12 => (year + 1, 1),
+            month => (year, month + 1),
+        };
+    }
+
+    months
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn month(year: i32, month: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn partition_months_roll_over_to_next_year() {
+        let from = Utc.from_utc_datetime(
+            &NaiveDate::from_ymd_opt(2022, 11, 30)
+                .unwrap()
+                .and_hms_opt(23, 59, 59)
+                .unwrap(),
+        );
+
+        assert_eq!(
+            partition_months(from, 3),
+            vec![
+                month(2022, 11),
+                month(2022, 12),
+                month(2023, 1),
+                month(2023, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn partition_months_without_months_ahead_is_current_month() {
+        let from = Utc.from_utc_datetime(&month(2022, 12));
+
+        assert_eq!(partition_months(from, 0), vec![month(2022, 12)]);
+    }
 }
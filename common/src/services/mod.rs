@@ -1,5 +1,17 @@
+pub mod api_keys;
+pub mod channel_leases;
 pub mod channels;
+pub mod channels_old_names;
+pub mod cold_archive;
+pub mod collection_gaps;
+pub mod collector_instances;
+pub mod irc_tokens;
 pub mod messages;
 pub mod resubs;
+pub mod retention;
+pub mod room_state_changes;
+pub mod stream_session_changes;
+pub mod stream_sessions;
+pub mod user_erasures;
 pub mod users;
 pub mod users_old_names;
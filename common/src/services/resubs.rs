@@ -1,7 +1,7 @@
 use diesel::{prelude::*, PgConnection};
-use error_stack::IntoReport;
+use error_stack::{IntoReport, ResultExt};
 
-use crate::{models::resub::{Resub, NewResub}, schema::resubs};
+use crate::{models::resub::{Resub, NewResub}, schema::{messages, resubs}};
 
 pub fn create_resub_return(
     db_conn: &PgConnection,
@@ -12,3 +12,17 @@ pub fn create_resub_return(
         .get_result(db_conn)
         .into_report()
 }
+
+/// Deletes resubs that no message points to anymore
+pub fn delete_orphaned(
+    db_conn: &PgConnection,
+) -> error_stack::Result<usize, diesel::result::Error> {
+    let referenced = messages::table
+        .select(messages::resub_id)
+        .filter(messages::resub_id.is_not_null());
+
+    diesel::delete(resubs::table.filter(resubs::id.nullable().ne_all(referenced)))
+        .execute(db_conn)
+        .into_report()
+        .attach_printable("database error: couldn't delete orphaned resubs")
+}
@@ -0,0 +1,181 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Duration, Utc};
+use diesel::{prelude::*, PgConnection};
+use error_stack::{IntoReport, Result, ResultExt};
+
+use crate::{
+    models::{channel::Channel, message::Message},
+    schema::{channels, messages, resubs},
+};
+
+/// advisory lock held while pruning, so only one collector instance prunes at a time
+const PRUNE_LOCK_KEY: i64 = 0x7463_685f_7072_756e;
+
+#[derive(Debug)]
+pub enum RetentionError {
+    Database,
+    /// messages couldn't be archived, so they weren't deleted
+    Archive,
+}
+
+impl Display for RetentionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RetentionError::Database => write!(f, "Database error"),
+            RetentionError::Archive => write!(f, "Couldn't archive messages"),
+        }
+    }
+}
+
+impl std::error::Error for RetentionError {}
+
+/// What was removed from one channel
+#[derive(Debug)]
+pub struct PruneReport {
+    pub channel_name: String,
+    pub messages: usize,
+    pub resubs: usize,
+}
+
+/// Channels that have `retention_days` set
+pub fn get_channels_with_retention(
+    db_conn: &PgConnection,
+) -> Result<Vec<Channel>, diesel::result::Error> {
+    channels::table
+        .filter(channels::retention_days.is_not_null())
+        .order(channels::id.asc())
+        .load(db_conn)
+        .into_report()
+        .attach_printable("database error: couldn't get channels with retention")
+}
+
+/// Oldest messages of channel sent before `older_than`, at most `limit` of them
+pub fn get_expired_messages(
+    db_conn: &PgConnection,
+    channel_id: i32,
+    older_than: DateTime<Utc>,
+    limit: i64,
+) -> Result<Vec<Message>, diesel::result::Error> {
+    messages::table
+        .filter(messages::channel_id.eq(channel_id))
+        .filter(messages::send_time.lt(older_than))
+        .order(messages::send_time.asc())
+        .limit(limit)
+        .load(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't get expired messages of channel_id {channel_id}")
+        })
+}
+
+/// Deletes `messages` together with their resubs, returns number of deleted messages and resubs
+pub fn delete_messages(
+    db_conn: &PgConnection,
+    messages: &[Message],
+) -> Result<(usize, usize), diesel::result::Error> {
+    let message_ids: Vec<i64> = messages.iter().map(|message| message.id).collect();
+    let resub_ids: Vec<i32> = messages
+        .iter()
+        .filter_map(|message| message.resub_id)
+        .collect();
+    // bounds on send_time let postgres skip partitions of other months
+    let oldest = messages.iter().map(|message| message.send_time).min();
+    let newest = messages.iter().map(|message| message.send_time).max();
+    let (Some(oldest), Some(newest)) = (oldest, newest) else {
+        return Ok((0, 0));
+    };
+
+    db_conn
+        .transaction(|| {
+            // messages point to resubs, so they go first
+            let deleted_messages = diesel::delete(
+                messages::table
+                    .filter(messages::id.eq_any(&message_ids))
+                    .filter(messages::send_time.between(oldest, newest)),
+            )
+            .execute(db_conn)?;
+            let deleted_resubs =
+                diesel::delete(resubs::table.filter(resubs::id.eq_any(&resub_ids)))
+                    .execute(db_conn)?;
+
+            Ok((deleted_messages, deleted_resubs))
+        })
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!(
+                "database error: couldn't delete {} messages",
+                message_ids.len()
+            )
+        })
+}
+
+/// Deletes messages of `channel` older than its retention in batches of `batch_size`.
+/// Every batch is passed to `archive` first and isn't deleted if archiving fails.
+pub fn prune_channel<F>(
+    db_conn: &PgConnection,
+    channel: &Channel,
+    now: DateTime<Utc>,
+    batch_size: i64,
+    mut archive: F,
+) -> Result<PruneReport, RetentionError>
+where
+    F: FnMut(&Channel, &[Message]) -> Result<(), RetentionError>,
+{
+    let mut report = PruneReport {
+        channel_name: channel.channel_name.clone(),
+        messages: 0,
+        resubs: 0,
+    };
+
+    let Some(retention_days) = channel.retention_days else {
+        return Ok(report);
+    };
+    let older_than = now - Duration::days(retention_days as i64);
+
+    loop {
+        let batch = get_expired_messages(db_conn, channel.id, older_than, batch_size)
+            .change_context(RetentionError::Database)?;
+
+        if batch.is_empty() {
+            return Ok(report);
+        }
+
+        archive(channel, &batch)?;
+
+        let (messages, resubs) =
+            delete_messages(db_conn, &batch).change_context(RetentionError::Database)?;
+        report.messages += messages;
+        report.resubs += resubs;
+
+        // last batch, no need to ask again
+        if (batch.len() as i64) < batch_size {
+            return Ok(report);
+        }
+    }
+}
+
+#[derive(QueryableByName)]
+struct Locked {
+    #[sql_type = "diesel::sql_types::Bool"]
+    locked: bool,
+}
+
+/// Takes session lock for pruning, false if other connection holds it
+pub fn try_lock(db_conn: &PgConnection) -> Result<bool, diesel::result::Error> {
+    diesel::sql_query("SELECT pg_try_advisory_lock($1) AS locked")
+        .bind::<diesel::sql_types::BigInt, _>(PRUNE_LOCK_KEY)
+        .get_result::<Locked>(db_conn)
+        .map(|result| result.locked)
+        .into_report()
+        .attach_printable("database error: couldn't take prune lock")
+}
+
+pub fn unlock(db_conn: &PgConnection) -> Result<(), diesel::result::Error> {
+    diesel::sql_query("SELECT pg_advisory_unlock($1) AS locked")
+        .bind::<diesel::sql_types::BigInt, _>(PRUNE_LOCK_KEY)
+        .get_result::<Locked>(db_conn)
+        .map(|_| ())
+        .into_report()
+        .attach_printable("database error: couldn't release prune lock")
+}
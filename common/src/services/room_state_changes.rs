@@ -0,0 +1,38 @@
+use diesel::{prelude::*, PgConnection};
+use error_stack::{IntoReport, Result, ResultExt};
+
+use crate::{
+    models::room_state_change::{NewRoomStateChange, RoomStateChange},
+    schema::room_state_changes,
+};
+
+pub fn create(
+    db_conn: &PgConnection,
+    new_room_state_change: NewRoomStateChange,
+) -> Result<usize, diesel::result::Error> {
+    let channel_id = new_room_state_change.channel_id;
+
+    diesel::insert_into(room_state_changes::table)
+        .values(new_room_state_change)
+        .execute(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!(
+                "database error: couldn't insert room state change for channel_id: {channel_id}"
+            )
+        })
+}
+
+pub fn get_by_channel_id(
+    db_conn: &PgConnection,
+    channel_id: i32,
+) -> Result<Vec<RoomStateChange>, diesel::result::Error> {
+    room_state_changes::table
+        .filter(room_state_changes::channel_id.eq(channel_id))
+        .order(room_state_changes::change_time.asc())
+        .load(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't get room state changes for channel_id: {channel_id}")
+        })
+}
@@ -0,0 +1,36 @@
+use diesel::{prelude::*, PgConnection};
+use error_stack::{IntoReport, Result, ResultExt};
+
+use crate::{
+    models::stream_session_change::{NewStreamSessionChange, StreamSessionChange},
+    schema::stream_session_changes,
+};
+
+pub fn create(
+    db_conn: &PgConnection,
+    new_change: NewStreamSessionChange,
+) -> Result<usize, diesel::result::Error> {
+    let stream_session_id = new_change.stream_session_id;
+
+    diesel::insert_into(stream_session_changes::table)
+        .values(new_change)
+        .execute(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't insert change of stream session with id: {stream_session_id}")
+        })
+}
+
+pub fn get_by_stream_session_id(
+    db_conn: &PgConnection,
+    stream_session_id: i32,
+) -> Result<Vec<StreamSessionChange>, diesel::result::Error> {
+    stream_session_changes::table
+        .filter(stream_session_changes::stream_session_id.eq(stream_session_id))
+        .order(stream_session_changes::change_time.asc())
+        .load(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't get changes of stream session with id: {stream_session_id}")
+        })
+}
@@ -0,0 +1,153 @@
+use chrono::{DateTime, Utc};
+use diesel::{prelude::*, PgConnection};
+use error_stack::{IntoReport, Result, ResultExt};
+
+use crate::{
+    models::{
+        stream_session::{NewStreamSession, StreamSession},
+        stream_session_change::NewStreamSessionChange,
+    },
+    schema::stream_sessions,
+};
+
+use super::stream_session_changes;
+
+pub fn get_by_id(
+    db_conn: &PgConnection,
+    stream_session_id: i32,
+) -> Result<Option<StreamSession>, diesel::result::Error> {
+    stream_sessions::table
+        .find(stream_session_id)
+        .first(db_conn)
+        .optional()
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't get stream session with id: {stream_session_id}")
+        })
+}
+
+pub fn get_by_channel_id(
+    db_conn: &PgConnection,
+    channel_id: i32,
+) -> Result<Vec<StreamSession>, diesel::result::Error> {
+    stream_sessions::table
+        .filter(stream_sessions::channel_id.eq(channel_id))
+        .order(stream_sessions::started_at.desc())
+        .load(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't get stream sessions of channel_id: {channel_id}")
+        })
+}
+
+/// Returns session that wasn't ended yet
+pub fn get_open_session(
+    db_conn: &PgConnection,
+    channel_id: i32,
+) -> Result<Option<StreamSession>, diesel::result::Error> {
+    stream_sessions::table
+        .filter(stream_sessions::channel_id.eq(channel_id))
+        .filter(stream_sessions::ended_at.is_null())
+        .order(stream_sessions::started_at.desc())
+        .first(db_conn)
+        .optional()
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't get open stream session of channel_id: {channel_id}")
+        })
+}
+
+/// Returns session during which channel was live at `timestamp`
+pub fn get_session_at(
+    db_conn: &PgConnection,
+    channel_id: i32,
+    timestamp: DateTime<Utc>,
+) -> Result<Option<StreamSession>, diesel::result::Error> {
+    stream_sessions::table
+        .filter(stream_sessions::channel_id.eq(channel_id))
+        .filter(stream_sessions::started_at.le(timestamp))
+        .filter(
+            stream_sessions::ended_at
+                .is_null()
+                .or(stream_sessions::ended_at.ge(timestamp)),
+        )
+        .order(stream_sessions::started_at.desc())
+        .first(db_conn)
+        .optional()
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't get stream session of channel_id: {channel_id} at {timestamp}")
+        })
+}
+
+pub fn create(
+    db_conn: &PgConnection,
+    new_stream_session: NewStreamSession,
+) -> Result<StreamSession, diesel::result::Error> {
+    log::trace!(
+        "inserting stream session into db: twitch_stream_id: {}",
+        new_stream_session.twitch_stream_id
+    );
+
+    let twitch_stream_id = new_stream_session.twitch_stream_id.clone();
+
+    diesel::insert_into(stream_sessions::table)
+        .values(new_stream_session)
+        .get_result(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't create stream session: {twitch_stream_id}")
+        })
+}
+
+pub fn end_session(
+    db_conn: &PgConnection,
+    stream_session_id: i32,
+    ended_at: DateTime<Utc>,
+) -> Result<usize, diesel::result::Error> {
+    diesel::update(stream_sessions::table)
+        .filter(stream_sessions::id.eq(stream_session_id))
+        .set(stream_sessions::ended_at.eq(ended_at))
+        .execute(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't end stream session with id: {stream_session_id}")
+        })
+}
+
+/// Records title/category change if they differ from the ones stored in `stream_session`
+pub fn check_and_fix_info(
+    db_conn: &PgConnection,
+    stream_session: StreamSession,
+    title: &str,
+    category_id: &str,
+    category_name: &str,
+    timestamp: DateTime<Utc>,
+) -> Result<(), diesel::result::Error> {
+    if stream_session.title == title && stream_session.category_id == category_id {
+        return Ok(());
+    }
+
+    stream_session_changes::create(
+        db_conn,
+        NewStreamSessionChange {
+            stream_session_id: stream_session.id,
+            change_time: timestamp,
+            title: title.to_owned(),
+            category_id: category_id.to_owned(),
+            category_name: category_name.to_owned(),
+        },
+    )?;
+
+    diesel::update(stream_sessions::table)
+        .filter(stream_sessions::id.eq(stream_session.id))
+        .set((
+            stream_sessions::title.eq(title),
+            stream_sessions::category_id.eq(category_id),
+            stream_sessions::category_name.eq(category_name),
+        ))
+        .execute(db_conn)
+        .into_report()?;
+
+    Ok(())
+}
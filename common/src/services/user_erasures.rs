@@ -0,0 +1,37 @@
+use diesel::{prelude::*, PgConnection};
+use error_stack::{IntoReport, Result, ResultExt};
+
+use crate::{
+    models::user_erasure::{NewUserErasure, UserErasure},
+    schema::user_erasures,
+};
+
+pub fn create(
+    db_conn: &PgConnection,
+    new_user_erasure: NewUserErasure,
+) -> Result<UserErasure, diesel::result::Error> {
+    let twitch_user_id = new_user_erasure.twitch_user_id.clone();
+
+    diesel::insert_into(user_erasures::table)
+        .values(new_user_erasure)
+        .get_result(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't insert erasure of twitch user id: {twitch_user_id}")
+        })
+}
+
+/// Erasures of user with `twitch_user_id`, oldest first
+pub fn get_by_twitch_user_id(
+    db_conn: &PgConnection,
+    twitch_user_id: &str,
+) -> Result<Vec<UserErasure>, diesel::result::Error> {
+    user_erasures::table
+        .filter(user_erasures::twitch_user_id.eq(twitch_user_id))
+        .order(user_erasures::erased_at.asc())
+        .load(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't get erasures of twitch user id: {twitch_user_id}")
+        })
+}
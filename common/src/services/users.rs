@@ -1,12 +1,15 @@
 use crate::{
-    models::user::{NewUser, User},
-    schema::users,
+    models::{
+        user::{NewUser, User},
+        user_erasure::{ErasureMode, NewUserErasure, UserErasure},
+    },
+    schema::{messages, users, users_old_names as users_old_names_table},
 };
 use chrono::{DateTime, Utc};
 use diesel::prelude::*;
-use error_stack::{IntoReport, Result, ResultExt};
+use error_stack::{IntoReport, Report, Result, ResultExt};
 
-use super::users_old_names;
+use super::{resubs, user_erasures, users_old_names};
 
 pub fn get_user_by_user_id(
     user_id: &str,
@@ -55,4 +58,74 @@ pub fn check_and_fix_username(db_conn: &PgConnection, user: User, user_name: &st
         .into_report()?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Erases user with `twitch_user_id` from every channel: messages are deleted or anonymized
+/// depending on `mode`, old names and the user are deleted and erasure is recorded in audit log,
+/// all in one transaction. None when there's no such user.
+///
+/// Files written by retention and cold archive aren't touched, audit record says so with
+/// `archives_processed` set to false.
+pub fn erase_user(
+    db_conn: &PgConnection,
+    twitch_user_id: &str,
+    mode: ErasureMode,
+    requested_by: &str,
+    reason: Option<&str>,
+) -> Result<Option<UserErasure>, diesel::result::Error> {
+    db_conn
+        .transaction::<_, Report<diesel::result::Error>, _>(|| {
+            let Some(user) = get_user_by_user_id(twitch_user_id, db_conn)? else {
+                return Ok(None);
+            };
+
+            // messages and old names point to the user, so they go first
+            let user_messages = messages::table.filter(messages::user_id.eq(user.id));
+            let messages = match mode {
+                ErasureMode::Delete => {
+                    let deleted = diesel::delete(user_messages)
+                        .execute(db_conn)
+                        .into_report()?;
+                    resubs::delete_orphaned(db_conn)?;
+
+                    deleted
+                }
+                ErasureMode::Anonymize => diesel::update(user_messages)
+                    .set((
+                        messages::msg.eq(None::<String>),
+                        messages::user_id.eq(None::<i32>),
+                    ))
+                    .execute(db_conn)
+                    .into_report()?,
+            };
+
+            let old_names = diesel::delete(
+                users_old_names_table::table.filter(users_old_names_table::user_id.eq(user.id)),
+            )
+            .execute(db_conn)
+            .into_report()?;
+
+            diesel::delete(users::table.filter(users::id.eq(user.id)))
+                .execute(db_conn)
+                .into_report()?;
+
+            let erasure = user_erasures::create(
+                db_conn,
+                NewUserErasure {
+                    twitch_user_id: twitch_user_id.to_owned(),
+                    mode,
+                    requested_by: requested_by.to_owned(),
+                    reason: reason.map(str::to_owned),
+                    messages: messages as i64,
+                    old_names: old_names as i64,
+                    erased_at: Utc::now(),
+                    archives_processed: false,
+                },
+            )?;
+
+            Ok(Some(erasure))
+        })
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't erase user with twitch user id: {twitch_user_id}")
+        })
+}
@@ -2,7 +2,10 @@ use chrono::{DateTime, Utc};
 use diesel::{prelude::*, PgConnection};
 use error_stack::{IntoReport, ResultExt};
 
-use crate::{models::user_old_name::NewUserOldName, schema::users_old_names};
+use crate::{
+    models::user_old_name::{NewUserOldName, UserOldName},
+    schema::users_old_names,
+};
 
 pub fn create(
     db_conn: &PgConnection,
@@ -20,3 +23,18 @@ pub fn create(
         .into_report()
         .attach_printable_lazy(|| format!("values: user_id: {}, username: {}", user_id, old_name))
 }
+
+/// Old names of user, the most recent first
+pub fn get_by_user_id(
+    db_conn: &PgConnection,
+    user_id: i32,
+) -> error_stack::Result<Vec<UserOldName>, diesel::result::Error> {
+    users_old_names::table
+        .filter(users_old_names::user_id.eq(user_id))
+        .order(users_old_names::first_time_with_new_name.desc())
+        .load(db_conn)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("database error: couldn't get old names of user with id: {user_id}")
+        })
+}
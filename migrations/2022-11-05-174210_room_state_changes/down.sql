@@ -0,0 +1 @@
+DROP TABLE room_state_changes;
\ No newline at end of file
@@ -0,0 +1,17 @@
+CREATE TABLE room_state_changes (
+    id SERIAL PRIMARY KEY NOT NULL,
+    uuid UUID UNIQUE NOT NULL DEFAULT uuid_generate_v4(),
+    channel_id INTEGER NOT NULL,
+    change_time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    -- every mode column is null when the ROOMSTATE didn't change that mode
+    emote_only BOOLEAN,
+    followers_only_minutes INTEGER, -- -1 == followers only disabled
+    r9k BOOLEAN,
+    slow_mode_seconds INTEGER, -- 0 == slow mode disabled
+    subscribers_only BOOLEAN,
+
+    CONSTRAINT FK_room_state_changes_channels FOREIGN KEY(channel_id)
+        REFERENCES channels(id)
+);
+
+CREATE INDEX room_state_changes_channel_id_change_time_idx ON room_state_changes ( channel_id, change_time );
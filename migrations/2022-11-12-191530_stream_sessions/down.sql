@@ -0,0 +1,3 @@
+ALTER TABLE messages DROP COLUMN stream_session_id;
+DROP TABLE stream_session_changes;
+DROP TABLE stream_sessions;
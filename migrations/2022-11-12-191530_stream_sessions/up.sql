@@ -0,0 +1,39 @@
+CREATE TABLE stream_sessions (
+    id SERIAL PRIMARY KEY NOT NULL,
+    uuid UUID UNIQUE NOT NULL DEFAULT uuid_generate_v4(),
+    channel_id INTEGER NOT NULL,
+    twitch_stream_id VARCHAR NOT NULL,
+    title VARCHAR NOT NULL,
+    category_id VARCHAR NOT NULL,
+    category_name VARCHAR NOT NULL,
+    started_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    ended_at TIMESTAMP WITH TIME ZONE, -- null while stream is live
+
+    CONSTRAINT FK_stream_sessions_channels FOREIGN KEY(channel_id)
+        REFERENCES channels(id)
+);
+
+CREATE INDEX stream_sessions_channel_id_started_at_idx ON stream_sessions ( channel_id, started_at );
+
+-- every title/category update while live, stream_sessions keeps the latest values
+CREATE TABLE stream_session_changes (
+    id SERIAL PRIMARY KEY NOT NULL,
+    uuid UUID UNIQUE NOT NULL DEFAULT uuid_generate_v4(),
+    stream_session_id INTEGER NOT NULL,
+    change_time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    title VARCHAR NOT NULL,
+    category_id VARCHAR NOT NULL,
+    category_name VARCHAR NOT NULL,
+
+    CONSTRAINT FK_stream_session_changes_stream_sessions FOREIGN KEY(stream_session_id)
+        REFERENCES stream_sessions(id)
+);
+
+CREATE INDEX stream_session_changes_stream_session_id_idx ON stream_session_changes ( stream_session_id );
+
+ALTER TABLE messages ADD COLUMN stream_session_id INTEGER; -- null if channel wasn't live
+
+ALTER TABLE messages ADD CONSTRAINT FK_messages_stream_sessions FOREIGN KEY(stream_session_id)
+    REFERENCES stream_sessions(id);
+
+CREATE INDEX messages_stream_session_id_idx ON messages ( stream_session_id );
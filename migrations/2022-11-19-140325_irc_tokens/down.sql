@@ -0,0 +1 @@
+DROP TABLE irc_tokens;
\ No newline at end of file
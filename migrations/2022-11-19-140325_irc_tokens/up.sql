@@ -0,0 +1,9 @@
+-- user access tokens of the bot account collector logs into irc with
+CREATE TABLE irc_tokens (
+    id SERIAL PRIMARY KEY NOT NULL,
+    login VARCHAR UNIQUE NOT NULL,
+    access_token VARCHAR NOT NULL,
+    refresh_token VARCHAR NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP WITH TIME ZONE -- null if token never expires
+);
@@ -0,0 +1 @@
+DROP TABLE channels_old_names;
\ No newline at end of file
@@ -0,0 +1,11 @@
+CREATE TABLE channels_old_names (
+    id SERIAL PRIMARY KEY NOT NULL,
+    channel_id INTEGER NOT NULL,
+    channel_name VARCHAR NOT NULL,
+    first_time_with_new_name TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    CONSTRAINT FK_channels_old_names_channels FOREIGN KEY(channel_id)
+        REFERENCES channels(id)
+);
+
+CREATE INDEX channels_old_names_channel_id_idx ON channels_old_names ( channel_id );
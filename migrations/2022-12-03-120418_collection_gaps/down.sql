@@ -0,0 +1,3 @@
+DROP TABLE collection_gaps;
+ALTER TABLE channels DROP COLUMN last_collected_at;
+ALTER TABLE channels DROP COLUMN collecting_since;
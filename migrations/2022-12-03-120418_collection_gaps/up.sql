@@ -0,0 +1,18 @@
+-- collector moves last_collected_at forward while it's recording the channel
+ALTER TABLE channels ADD COLUMN collecting_since TIMESTAMP WITH TIME ZONE; -- null if never joined
+ALTER TABLE channels ADD COLUMN last_collected_at TIMESTAMP WITH TIME ZONE;
+
+-- periods between collecting_since and last_collected_at during which nothing was recorded
+CREATE TABLE collection_gaps (
+    id SERIAL PRIMARY KEY NOT NULL,
+    uuid UUID UNIQUE NOT NULL DEFAULT uuid_generate_v4(),
+    channel_id INTEGER NOT NULL,
+    started_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    ended_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    reason VARCHAR NOT NULL,
+
+    CONSTRAINT FK_collection_gaps_channels FOREIGN KEY(channel_id)
+        REFERENCES channels(id)
+);
+
+CREATE INDEX collection_gaps_channel_id_started_at_idx ON collection_gaps ( channel_id, started_at );
@@ -0,0 +1,2 @@
+DROP TABLE channel_leases;
+DROP TABLE collector_instances;
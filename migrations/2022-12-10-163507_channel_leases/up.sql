@@ -0,0 +1,19 @@
+-- collector instances sharing channels, instance that stopped updating last_seen_at is dead
+CREATE TABLE collector_instances (
+    id SERIAL PRIMARY KEY NOT NULL,
+    instance_id VARCHAR UNIQUE NOT NULL,
+    last_seen_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+-- which instance records the channel, expired lease can be taken over by any instance
+CREATE TABLE channel_leases (
+    id SERIAL PRIMARY KEY NOT NULL,
+    channel_id INTEGER UNIQUE NOT NULL,
+    instance_id VARCHAR NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+
+    CONSTRAINT FK_channel_leases_channels FOREIGN KEY(channel_id)
+        REFERENCES channels(id)
+);
+
+CREATE INDEX channel_leases_instance_id_idx ON channel_leases ( instance_id );
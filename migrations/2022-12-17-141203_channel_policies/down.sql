@@ -0,0 +1,7 @@
+UPDATE messages SET msg = '' WHERE msg IS NULL;
+ALTER TABLE messages ALTER COLUMN msg SET NOT NULL;
+
+ALTER TABLE channels DROP COLUMN paused;
+ALTER TABLE channels DROP COLUMN retention_days;
+ALTER TABLE channels DROP COLUMN store_text;
+ALTER TABLE channels DROP COLUMN stored_events;
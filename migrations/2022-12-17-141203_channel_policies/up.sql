@@ -0,0 +1,8 @@
+-- collection policy of the channel, collector keeps it in sync with `channels` in config
+ALTER TABLE channels ADD COLUMN stored_events VARCHAR[] NOT NULL DEFAULT '{message,action,bits,sub,room_state}';
+ALTER TABLE channels ADD COLUMN store_text BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE channels ADD COLUMN retention_days INTEGER; -- null keeps everything forever
+ALTER TABLE channels ADD COLUMN paused BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- null when channel's policy allows storing only metadata of messages
+ALTER TABLE messages ALTER COLUMN msg DROP NOT NULL;
@@ -0,0 +1 @@
+DROP INDEX messages_channel_id_send_time_idx;
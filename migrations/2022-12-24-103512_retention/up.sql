@@ -0,0 +1,2 @@
+-- pruning looks for the oldest messages of a channel
+CREATE INDEX messages_channel_id_send_time_idx ON messages ( channel_id, send_time );
@@ -0,0 +1,47 @@
+CREATE TABLE messages_unpartitioned (
+    id BIGINT PRIMARY KEY NOT NULL DEFAULT nextval('messages_id_seq'),
+    uuid UUID UNIQUE NOT NULL DEFAULT uuid_generate_v4(),
+    msg VARCHAR,
+    msg_type VARCHAR NOT NULL,
+    user_id INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    resub_id INTEGER UNIQUE,
+    send_time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    bits BIGINT,
+    stream_session_id INTEGER,
+
+    CONSTRAINT FK_messages_users FOREIGN KEY(user_id)
+        REFERENCES users(id),
+
+    CONSTRAINT FK_messages_channels FOREIGN KEY(channel_id)
+        REFERENCES channels(id),
+
+    CONSTRAINT FK_messages_resubs FOREIGN KEY(resub_id)
+        REFERENCES resubs(id),
+
+    CONSTRAINT FK_messages_stream_sessions FOREIGN KEY(stream_session_id)
+        REFERENCES stream_sessions(id)
+);
+
+INSERT INTO messages_unpartitioned (
+    id, uuid, msg, msg_type, user_id, channel_id, resub_id, send_time, bits, stream_session_id
+)
+SELECT id, uuid, msg, msg_type, user_id, channel_id, resub_id, send_time, bits, stream_session_id
+FROM messages;
+
+ALTER SEQUENCE messages_id_seq OWNED BY NONE;
+DROP TABLE messages; -- drops partitions too
+DROP FUNCTION create_messages_partition(TIMESTAMP);
+
+ALTER TABLE messages_unpartitioned RENAME TO messages;
+ALTER SEQUENCE messages_id_seq OWNED BY messages.id;
+
+ALTER INDEX messages_unpartitioned_pkey RENAME TO messages_pkey;
+ALTER INDEX messages_unpartitioned_uuid_key RENAME TO messages_uuid_key;
+ALTER INDEX messages_unpartitioned_resub_id_key RENAME TO messages_resub_id_key;
+
+CREATE INDEX messages_user_id_idx ON messages ( user_id );
+CREATE INDEX messages_channel_id_idx ON messages ( channel_id );
+CREATE INDEX messages_resub_id_idx ON messages ( resub_id );
+CREATE INDEX messages_stream_session_id_idx ON messages ( stream_session_id );
+CREATE INDEX messages_channel_id_send_time_idx ON messages ( channel_id, send_time );
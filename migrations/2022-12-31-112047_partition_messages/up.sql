@@ -0,0 +1,81 @@
+-- messages are split into monthly partitions by send_time, so time range queries only touch
+-- months they ask for. Primary and unique keys of partitioned table have to include send_time.
+CREATE TABLE messages_partitioned (
+    id BIGINT NOT NULL DEFAULT nextval('messages_id_seq'),
+    uuid UUID NOT NULL DEFAULT uuid_generate_v4(),
+    msg VARCHAR, -- null when channel's policy allows storing only metadata of messages
+    msg_type VARCHAR NOT NULL, -- message/bits/sub/action
+    user_id INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    resub_id INTEGER, -- null if not resub
+    send_time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    bits BIGINT,
+    stream_session_id INTEGER, -- null if channel wasn't live
+
+    PRIMARY KEY (id, send_time),
+    UNIQUE (uuid, send_time),
+    UNIQUE (resub_id, send_time),
+
+    CONSTRAINT FK_messages_users FOREIGN KEY(user_id)
+        REFERENCES users(id),
+
+    CONSTRAINT FK_messages_channels FOREIGN KEY(channel_id)
+        REFERENCES channels(id),
+
+    CONSTRAINT FK_messages_resubs FOREIGN KEY(resub_id)
+        REFERENCES resubs(id),
+
+    CONSTRAINT FK_messages_stream_sessions FOREIGN KEY(stream_session_id)
+        REFERENCES stream_sessions(id)
+) PARTITION BY RANGE (send_time);
+
+-- creates partition for month (in UTC) containing `month`, collector keeps creating them ahead
+CREATE FUNCTION create_messages_partition(month TIMESTAMP) RETURNS VOID AS $$
+DECLARE
+    month_start TIMESTAMP := date_trunc('month', month);
+BEGIN
+    EXECUTE format(
+        'CREATE TABLE IF NOT EXISTS %I PARTITION OF messages FOR VALUES FROM (%L) TO (%L)',
+        'messages_' || to_char(month_start, 'YYYY_MM'),
+        month_start AT TIME ZONE 'UTC',
+        (month_start + INTERVAL '1 month') AT TIME ZONE 'UTC'
+    );
+END;
+$$ LANGUAGE plpgsql;
+
+-- sequence would be dropped together with the old table
+ALTER SEQUENCE messages_id_seq OWNED BY NONE;
+ALTER TABLE messages RENAME TO messages_unpartitioned;
+ALTER TABLE messages_partitioned RENAME TO messages;
+ALTER SEQUENCE messages_id_seq OWNED BY messages.id;
+
+-- every month with messages and a few ahead
+SELECT create_messages_partition(month)
+FROM generate_series(
+    date_trunc('month', COALESCE(
+        (SELECT MIN(send_time) FROM messages_unpartitioned),
+        CURRENT_TIMESTAMP
+    ) AT TIME ZONE 'UTC'),
+    (CURRENT_TIMESTAMP AT TIME ZONE 'UTC') + INTERVAL '3 months',
+    INTERVAL '1 month'
+) AS month;
+
+INSERT INTO messages (
+    id, uuid, msg, msg_type, user_id, channel_id, resub_id, send_time, bits, stream_session_id
+)
+SELECT id, uuid, msg, msg_type, user_id, channel_id, resub_id, send_time, bits, stream_session_id
+FROM messages_unpartitioned;
+
+DROP TABLE messages_unpartitioned;
+
+-- names of the old table are free now
+ALTER INDEX messages_partitioned_pkey RENAME TO messages_pkey;
+ALTER INDEX messages_partitioned_uuid_send_time_key RENAME TO messages_uuid_send_time_key;
+ALTER INDEX messages_partitioned_resub_id_send_time_key RENAME TO messages_resub_id_send_time_key;
+
+CREATE INDEX messages_user_id_idx ON messages ( user_id );
+CREATE INDEX messages_channel_id_idx ON messages ( channel_id );
+CREATE INDEX messages_resub_id_idx ON messages ( resub_id );
+CREATE INDEX messages_stream_session_id_idx ON messages ( stream_session_id );
+CREATE INDEX messages_channel_id_send_time_idx ON messages ( channel_id, send_time );
+CREATE INDEX messages_send_time_idx ON messages ( send_time );
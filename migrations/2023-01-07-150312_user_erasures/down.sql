@@ -0,0 +1,5 @@
+DROP TABLE user_erasures;
+
+-- anonymized messages have no user to go back to
+DELETE FROM messages WHERE user_id IS NULL;
+ALTER TABLE messages ALTER COLUMN user_id SET NOT NULL;
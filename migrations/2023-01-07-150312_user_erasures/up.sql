@@ -0,0 +1,17 @@
+-- messages of erased users can be kept without text and without the user
+ALTER TABLE messages ALTER COLUMN user_id DROP NOT NULL;
+
+-- audit log of erased users, written in the same transaction as the erasure
+CREATE TABLE user_erasures (
+    id SERIAL PRIMARY KEY NOT NULL,
+    uuid UUID UNIQUE NOT NULL DEFAULT uuid_generate_v4(),
+    twitch_user_id VARCHAR NOT NULL,
+    mode VARCHAR NOT NULL, -- delete or anonymize
+    requested_by VARCHAR NOT NULL,
+    reason VARCHAR,
+    messages BIGINT NOT NULL, -- deleted or anonymized
+    old_names BIGINT NOT NULL,
+    erased_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX user_erasures_twitch_user_id_idx ON user_erasures ( twitch_user_id );
@@ -0,0 +1,10 @@
+-- keys of api clients, only sha256 of the key is stored, the key itself is shown once on creation
+CREATE TABLE api_keys (
+    id SERIAL PRIMARY KEY NOT NULL,
+    uuid UUID UNIQUE NOT NULL DEFAULT uuid_generate_v4(),
+    name VARCHAR NOT NULL,
+    key_hash VARCHAR UNIQUE NOT NULL,
+    scopes VARCHAR[] NOT NULL, -- read_public, read_moderation, admin, export
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    revoked_at TIMESTAMP WITH TIME ZONE -- null while key can be used
+);
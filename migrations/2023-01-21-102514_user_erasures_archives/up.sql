@@ -0,0 +1,3 @@
+-- files written by retention and cold archive aren't rewritten by erasure, so the audit log
+-- records whether copies of erased user in them were dealt with
+ALTER TABLE user_erasures ADD COLUMN archives_processed BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,147 @@
+use std::{fmt::Display, sync::Arc};
+
+use chrono::Utc;
+use common::{
+    models::channel::Channel,
+    services::channels::{check_and_fix_channel_name, get_channel_by_twitch_id},
+};
+use error_stack::{IntoReport, Result, ResultExt};
+use twitch_api2::{
+    helix::users::{GetUsersRequest, User},
+    types::UserId,
+    HelixClient,
+};
+use twitch_irc::{login::LoginCredentials, SecureTCPTransport, TwitchIRCClient};
+
+use crate::{
+    config_reload::ConfigInterval,
+    token_manager::TokenManager,
+    twitch_watcher::{DbPool, JoinedChannels},
+};
+
+/// helix allows at most 100 user ids per get users request
+const MAX_CHANNELS_PER_REQUEST: usize = 100;
+
+#[derive(Debug)]
+pub enum ReconcileError {
+    Api,
+    DbPool,
+    Database,
+}
+
+impl Display for ReconcileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReconcileError::Api => write!(f, "Couldn't get channels from twitch api"),
+            ReconcileError::DbPool => write!(f, "Couldn't get connection to database from pool"),
+            ReconcileError::Database => write!(f, "Database error"),
+        }
+    }
+}
+
+impl std::error::Error for ReconcileError {}
+
+/// Periodically compares channel names with helix, records renames and rejoins renamed channels
+/// under their new login. Never returns, errors are only logged.
+pub async fn run<L: LoginCredentials>(
+    pool: DbPool,
+    helix_client: HelixClient<'static, reqwest::Client>,
+    token_manager: Arc<TokenManager>,
+    client: TwitchIRCClient<SecureTCPTransport, L>,
+    joined_channels: JoinedChannels,
+) {
+    let mut interval = ConfigInterval::new(|config| config.channel_reconcile_interval_secs).await;
+
+    loop {
+        interval.tick().await;
+
+        let channels = joined_channels.read().await.clone();
+
+        let result = reconcile(
+            &pool,
+            &helix_client,
+            &token_manager,
+            &client,
+            &channels,
+            &joined_channels,
+        )
+        .await;
+
+        if let Err(err) = result {
+            error!("couldn't reconcile channel names: {err:?}");
+        }
+    }
+}
+
+async fn reconcile<L: LoginCredentials>(
+    pool: &DbPool,
+    helix_client: &HelixClient<'static, reqwest::Client>,
+    token_manager: &TokenManager,
+    client: &TwitchIRCClient<SecureTCPTransport, L>,
+    channels: &[Channel],
+    joined_channels: &JoinedChannels,
+) -> Result<(), ReconcileError> {
+    for channels in channels.chunks(MAX_CHANNELS_PER_REQUEST) {
+        let users: Vec<User> = token_manager
+            .call(|token| {
+                let request = GetUsersRequest::builder()
+                    .id(channels
+                        .iter()
+                        .map(|channel| UserId::new(channel.twitch_channel_id.clone()))
+                        .collect())
+                    .build();
+
+                async move { helix_client.req_get(request, &token).await }
+            })
+            .await
+            .change_context(ReconcileError::Api)?
+            .data;
+
+        let db_conn = pool
+            .get()
+            .into_report()
+            .change_context(ReconcileError::DbPool)?;
+
+        for user in users {
+            // name stored in db is the one channel is currently joined under
+            let channel = get_channel_by_twitch_id(&db_conn, user.id.as_str())
+                .change_context(ReconcileError::Database)?;
+
+            let Some(channel) = channel else {
+                continue;
+            };
+
+            if channel.channel_name == user.login.as_str() {
+                continue;
+            }
+
+            let old_name = channel.channel_name.clone();
+
+            let channel =
+                check_and_fix_channel_name(&db_conn, channel, user.login.as_str(), Utc::now())
+                    .change_context(ReconcileError::Database)?;
+
+            warn!(
+                "channel {old_name} was renamed to {}, rejoining. Update channels in config",
+                user.login
+            );
+
+            client.part(old_name);
+            if let Err(err) = client.join(user.login.to_string()) {
+                error!("couldn't join renamed channel {}: {err}", user.login);
+            }
+
+            // so shutdown parts the channel under its new name
+            if let Some(joined) = joined_channels
+                .write()
+                .await
+                .iter_mut()
+                .find(|joined| joined.id == channel.id)
+            {
+                *joined = channel;
+            }
+        }
+    }
+
+    Ok(())
+}
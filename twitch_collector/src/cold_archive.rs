@@ -0,0 +1,336 @@
+use std::{
+    fmt::Display,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{Duration, NaiveDate, Utc};
+use common::{
+    config::ColdArchiveConfig,
+    services::cold_archive::{self, ArchivedMessage, DropOutcome, PartitionContents},
+};
+use diesel::PgConnection;
+use error_stack::{IntoReport, Report, Result, ResultExt};
+
+use crate::{
+    parquet::{self, Column, Values},
+    s3::S3Client,
+    twitch_watcher::DbPool,
+};
+
+#[derive(Debug)]
+pub enum ColdArchiveError {
+    /// `[cold_archive]` section is missing in config
+    NotConfigured,
+    Database,
+    /// files couldn't be written, so partition wasn't dropped
+    Write,
+    /// partition changed after it was exported, so it wasn't dropped
+    Changed,
+}
+
+impl Display for ColdArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColdArchiveError::NotConfigured => write!(f, "[cold_archive] isn't configured"),
+            ColdArchiveError::Database => write!(f, "Database error"),
+            ColdArchiveError::Write => write!(f, "Couldn't write archive"),
+            ColdArchiveError::Changed => write!(f, "Partition changed after export"),
+        }
+    }
+}
+
+impl std::error::Error for ColdArchiveError {}
+
+/// What was archived from one monthly partition
+pub struct ArchivedPartition {
+    pub month: NaiveDate,
+    pub messages: usize,
+    pub files: usize,
+    pub resubs: usize,
+}
+
+enum Target {
+    Dir(PathBuf),
+    S3(S3Client),
+}
+
+impl Target {
+    async fn write(&self, key: &str, file: Vec<u8>) -> Result<(), ColdArchiveError> {
+        match self {
+            Target::Dir(dir) => {
+                let path = dir.join(key);
+
+                write_synced(&path, &file).map_err(|err| {
+                    Report::new(err)
+                        .change_context(ColdArchiveError::Write)
+                        .attach_printable(format!("couldn't write {}", path.display()))
+                })
+            }
+            Target::S3(client) => client
+                .put(key, file)
+                .await
+                .change_context(ColdArchiveError::Write),
+        }
+    }
+
+    /// Deletes file stored under `key`, there might be none
+    async fn delete(&self, key: &str) -> Result<(), ColdArchiveError> {
+        match self {
+            Target::Dir(dir) => match fs::remove_file(dir.join(key)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => Err(Report::new(err)
+                    .change_context(ColdArchiveError::Write)
+                    .attach_printable(format!("couldn't delete {key}"))),
+                _ => Ok(()),
+            },
+            Target::S3(client) => client
+                .delete(key)
+                .await
+                .change_context(ColdArchiveError::Write),
+        }
+    }
+
+    /// Size of file stored under `key`, `None` if it isn't there
+    async fn size(&self, key: &str) -> Result<Option<u64>, ColdArchiveError> {
+        match self {
+            Target::Dir(dir) => match fs::metadata(dir.join(key)) {
+                Ok(metadata) => Ok(Some(metadata.len())),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(Report::new(err).change_context(ColdArchiveError::Write)),
+            },
+            Target::S3(client) => client
+                .size(key)
+                .await
+                .change_context(ColdArchiveError::Write),
+        }
+    }
+}
+
+/// Writes `file` so it survives crash once this returns, partially written file never shows up
+/// under `path`
+fn write_synced(path: &Path, file: &[u8]) -> io::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let tmp_path = path.with_extension("parquet.tmp");
+
+    fs::create_dir_all(dir)?;
+
+    let mut tmp = File::create(&tmp_path)?;
+    tmp.write_all(file)?;
+    tmp.sync_all()?;
+
+    fs::rename(&tmp_path, path)?;
+    // rename is stored in directory, which has to be synced on its own
+    File::open(dir)?.sync_all()
+}
+
+/// Exports every partition of messages whose month is older than `cold_archive.older_than_days`
+/// to parquet files and drops it. Partition stays when anything fails, so it can be run again.
+pub async fn run(pool: DbPool) -> Result<Vec<ArchivedPartition>, ColdArchiveError> {
+    let config = get_config_async!()
+        .await
+        .cold_archive
+        .clone()
+        .ok_or_else(|| Report::new(ColdArchiveError::NotConfigured))?;
+
+    let target = match (&config.dir, &config.s3) {
+        (_, Some(s3)) => Target::S3(S3Client::new(s3.clone())),
+        (Some(dir), None) => Target::Dir(PathBuf::from(dir)),
+        (None, None) => return Err(Report::new(ColdArchiveError::NotConfigured)),
+    };
+
+    let cutoff = Utc::now() - Duration::days(config.older_than_days as i64);
+    let months = blocking(&pool, cold_archive::get_partition_months).await?;
+    let mut archived = vec![];
+
+    for month in months {
+        let (_, month_end) = cold_archive::month_bounds(month);
+        if month_end > cutoff {
+            continue;
+        }
+
+        let partition = archive_partition(&pool, &config, &target, month).await?;
+        info!(
+            "archived {} message(s) of {} into {} file(s)",
+            partition.messages,
+            month.format("%Y-%m"),
+            partition.files
+        );
+        archived.push(partition);
+    }
+
+    Ok(archived)
+}
+
+async fn archive_partition(
+    pool: &DbPool,
+    config: &ColdArchiveConfig,
+    target: &Target,
+    month: NaiveDate,
+) -> Result<ArchivedPartition, ColdArchiveError> {
+    let export_started = Utc::now();
+    let mut partition = ArchivedPartition {
+        month,
+        messages: 0,
+        files: 0,
+        resubs: 0,
+    };
+    let mut after_id = 0;
+    let mut written = vec![];
+
+    loop {
+        let rows_per_file = config.rows_per_file;
+        let messages = blocking(pool, move |db_conn| {
+            cold_archive::get_partition_messages(db_conn, month, after_id, rows_per_file)
+        })
+        .await?;
+
+        let Some(last) = messages.last() else {
+            break;
+        };
+        after_id = last.id;
+
+        // same rows end up in same files, so running again after failure overwrites them
+        let key = part_key(month, partition.files);
+        let file = to_parquet(&messages)?;
+        written.push((key.clone(), file.len() as u64));
+        target.write(&key, file).await?;
+
+        partition.messages += messages.len();
+        partition.files += 1;
+    }
+
+    // messages exist only in archive after drop, so everything has to be there first
+    for (key, size) in written {
+        let stored = target.size(&key).await?;
+        if stored != Some(size) {
+            return Err(
+                Report::new(ColdArchiveError::Write).attach_printable(format!(
+                    "{key} has size {stored:?} in archive instead of {size}, keeping partition"
+                )),
+            );
+        }
+    }
+
+    // parts after the written ones are left from earlier run that found more messages, readers
+    // would get their rows twice. Runs write parts in order and these are deleted from the last
+    // one, so parts of a partition never have gaps even after failure.
+    let mut stale_end = partition.files;
+    while target.size(&part_key(month, stale_end)).await?.is_some() {
+        stale_end += 1;
+    }
+    for stale in (partition.files..stale_end).rev() {
+        target.delete(&part_key(month, stale)).await?;
+    }
+
+    let exported = PartitionContents {
+        messages: partition.messages as i64,
+        last_id: (after_id > 0).then_some(after_id),
+    };
+    let outcome = blocking(pool, move |db_conn| {
+        cold_archive::drop_partition(db_conn, month, exported, export_started)
+    })
+    .await?;
+
+    match outcome {
+        DropOutcome::Dropped { resubs } => partition.resubs = resubs,
+        DropOutcome::Changed(contents) => {
+            return Err(
+                Report::new(ColdArchiveError::Changed).attach_printable(format!(
+                    "partition has {} message(s) up to id {:?} instead of exported {} up to id \
+                     {:?}, keeping it",
+                    contents.messages, contents.last_id, exported.messages, exported.last_id
+                )),
+            )
+        }
+        DropOutcome::UserErased => {
+            return Err(Report::new(ColdArchiveError::Changed)
+                .attach_printable("user was erased during export, keeping partition"))
+        }
+    }
+
+    Ok(partition)
+}
+
+fn part_key(month: NaiveDate, index: usize) -> String {
+    format!("messages/{}/part-{index:05}.parquet", month.format("%Y-%m"))
+}
+
+fn to_parquet(messages: &[ArchivedMessage]) -> Result<Vec<u8>, ColdArchiveError> {
+    fn column<T>(
+        name: &'static str,
+        messages: &[ArchivedMessage],
+        values: fn(Vec<Option<T>>) -> Values,
+        value: impl Fn(&ArchivedMessage) -> Option<T>,
+    ) -> Column {
+        Column {
+            name,
+            values: values(messages.iter().map(value).collect()),
+        }
+    }
+
+    parquet::write(&[
+        column("id", messages, Values::Int64, |message| Some(message.id)),
+        column("uuid", messages, Values::Utf8, |message| {
+            Some(message.uuid.to_string())
+        }),
+        column("send_time", messages, Values::Timestamp, |message| {
+            Some(message.send_time.timestamp_micros())
+        }),
+        column("msg_type", messages, Values::Utf8, |message| {
+            Some(message.msg_type.clone())
+        }),
+        column("msg", messages, Values::Utf8, |message| message.msg.clone()),
+        column("bits", messages, Values::Int64, |message| message.bits),
+        column("stream_session_id", messages, Values::Int32, |message| {
+            message.stream_session_id
+        }),
+        column("twitch_channel_id", messages, Values::Utf8, |message| {
+            Some(message.twitch_channel_id.clone())
+        }),
+        column("channel_name", messages, Values::Utf8, |message| {
+            Some(message.channel_name.clone())
+        }),
+        column("twitch_user_id", messages, Values::Utf8, |message| {
+            message.twitch_user_id.clone()
+        }),
+        column("username", messages, Values::Utf8, |message| {
+            message.username.clone()
+        }),
+        column(
+            "resub_cumulative_month",
+            messages,
+            Values::Int16,
+            |message| message.resub_cumulative_month,
+        ),
+        column("resub_tier", messages, Values::Int16, |message| {
+            message.resub_tier
+        }),
+    ])
+    .into_report()
+    .change_context(ColdArchiveError::Write)
+    .attach_printable("couldn't encode parquet file")
+}
+
+/// Runs diesel query away from async workers
+async fn blocking<T, F>(pool: &DbPool, query: F) -> Result<T, ColdArchiveError>
+where
+    T: Send + 'static,
+    F: FnOnce(&PgConnection) -> Result<T, diesel::result::Error> + Send + 'static,
+{
+    let pool = pool.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let db_conn = pool
+            .get()
+            .into_report()
+            .change_context(ColdArchiveError::Database)
+            .attach_printable("couldn't get connection to database from pool")?;
+
+        query(&db_conn).change_context(ColdArchiveError::Database)
+    })
+    .await
+    .into_report()
+    .change_context(ColdArchiveError::Database)
+    .attach_printable("database task panicked")?
+}
@@ -0,0 +1,200 @@
+use std::{collections::HashMap, sync::Arc};
+
+use common::{
+    config::{self, ChannelConfig},
+    models::channel::Channel,
+    services::channels::{get_channel_by_name, get_channel_by_old_name, set_policy},
+};
+use diesel::PgConnection;
+use twitch_api2::HelixClient;
+use twitch_irc::{login::LoginCredentials, SecureTCPTransport, TwitchIRCClient};
+
+use crate::{
+    coverage, metrics,
+    supervisor::{Backoff, TaskGroup},
+    token_manager::TokenManager,
+    twitch_watcher::{join_channel, DbPool, JoinedChannels},
+};
+
+/// Joins `channels` from config and follows changes of the list and of their policies on every
+/// config reload. Never returns.
+pub async fn run<L: LoginCredentials>(
+    pool: DbPool,
+    helix_client: HelixClient<'static, reqwest::Client>,
+    token_manager: Arc<TokenManager>,
+    client: TwitchIRCClient<SecureTCPTransport, L>,
+    joined_channels: JoinedChannels,
+) {
+    let mut config_updates = config::subscribe();
+    // join retries of every active channel from config, dropping the group stops them
+    let mut channels: HashMap<String, TaskGroup> = HashMap::new();
+
+    loop {
+        let config_channels = get_config_async!().await.channels.clone();
+        let active: Vec<&ChannelConfig> = ChannelConfig::active(&config_channels).collect();
+
+        let removed: Vec<String> = channels
+            .keys()
+            .filter(|name| !active.iter().any(|channel| &channel.name == *name))
+            .cloned()
+            .collect();
+
+        for channel in removed {
+            channels.remove(&channel);
+            part_channel(&pool, &client, &joined_channels, &channel).await;
+        }
+
+        // channels that are joined below get their policy stored while being resolved
+        for channel in &config_channels {
+            if channel.policy.paused || channels.contains_key(&channel.name) {
+                sync_policy(&pool, channel);
+            }
+        }
+
+        for channel in active {
+            if channels.contains_key(&channel.name) {
+                continue;
+            }
+
+            let mut tasks = TaskGroup::default();
+            let result = join_channel(&pool, &helix_client, &token_manager, &client, channel).await;
+
+            match result {
+                Ok(db_channel) => joined_channels.write().await.push(db_channel),
+                Err(err) => {
+                    // one bad channel shouldn't stop collecting from the others
+                    metrics::JOIN_FAILURES
+                        .with_label_values(&[&channel.name])
+                        .inc();
+                    error!("couldn't join channel {}: {err:?}", channel.name);
+
+                    tasks.spawn(retry_join_channel(
+                        pool.clone(),
+                        helix_client.clone(),
+                        token_manager.clone(),
+                        client.clone(),
+                        channel.clone(),
+                        joined_channels.clone(),
+                    ));
+                }
+            }
+
+            channels.insert(channel.name.clone(), tasks);
+        }
+
+        if config_updates.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Keeps trying to join `channel` with backoff until it succeeds
+async fn retry_join_channel<L: LoginCredentials>(
+    pool: DbPool,
+    helix_client: HelixClient<'static, reqwest::Client>,
+    token_manager: Arc<TokenManager>,
+    client: TwitchIRCClient<SecureTCPTransport, L>,
+    channel: ChannelConfig,
+    joined_channels: JoinedChannels,
+) {
+    let mut backoff = Backoff::default();
+
+    loop {
+        let delay = backoff.next_delay();
+        info!(
+            "Retrying to join channel {} in {}s",
+            channel.name,
+            delay.as_secs()
+        );
+        tokio::time::sleep(delay).await;
+
+        match join_channel(&pool, &helix_client, &token_manager, &client, &channel).await {
+            Ok(db_channel) => {
+                joined_channels.write().await.push(db_channel);
+                return;
+            }
+            Err(err) => {
+                metrics::JOIN_FAILURES
+                    .with_label_values(&[&channel.name])
+                    .inc();
+                error!("couldn't join channel {}: {err:?}", channel.name);
+            }
+        }
+    }
+}
+
+/// Stores policy of channel that's already in db, channels that were never resolved are skipped
+fn sync_policy(pool: &DbPool, channel: &ChannelConfig) {
+    let Ok(db_conn) = pool.get() else {
+        error!(
+            "couldn't get connection to database from pool to update policy of channel {}",
+            channel.name
+        );
+        return;
+    };
+
+    let result = find_channel(&db_conn, &channel.name).and_then(|db_channel| match db_channel {
+        Some(db_channel) => set_policy(&db_conn, db_channel, &channel.policy).map(|_| ()),
+        None => Ok(()),
+    });
+
+    if let Err(err) = result {
+        error!(
+            "couldn't update policy of channel {}: {err:?}",
+            channel.name
+        );
+    }
+}
+
+/// Finds channel by name from config, it can be the one channel had before rename
+fn find_channel(
+    db_conn: &PgConnection,
+    channel_name: &str,
+) -> error_stack::Result<Option<Channel>, diesel::result::Error> {
+    match get_channel_by_name(db_conn, channel_name)? {
+        Some(channel) => Ok(Some(channel)),
+        None => get_channel_by_old_name(db_conn, channel_name),
+    }
+}
+
+/// Parts channel that was removed from config or paused
+async fn part_channel<L: LoginCredentials>(
+    pool: &DbPool,
+    client: &TwitchIRCClient<SecureTCPTransport, L>,
+    joined_channels: &JoinedChannels,
+    channel_name: &str,
+) {
+    let Ok(db_conn) = pool.get() else {
+        error!("couldn't get connection to database from pool to part channel {channel_name}");
+        return;
+    };
+
+    let channel = match find_channel(&db_conn, channel_name) {
+        Ok(Some(channel)) => channel,
+        // never joined, nothing to part
+        Ok(None) => return,
+        Err(err) => {
+            error!("couldn't part channel {channel_name}: {err:?}");
+            return;
+        }
+    };
+
+    let mut joined_channels = joined_channels.write().await;
+
+    let Some(position) = joined_channels
+        .iter()
+        .position(|joined| joined.id == channel.id)
+    else {
+        return;
+    };
+
+    let channel = joined_channels.remove(position);
+
+    info!(
+        "Parting channel {}, it was removed from config or paused",
+        channel.channel_name
+    );
+
+    client.part(channel.channel_name.clone());
+    coverage::stop_recording(&db_conn, channel.id);
+}
@@ -0,0 +1,97 @@
+use std::time::{Duration, SystemTime};
+
+use common::config::{self, Config, CONFIG_FILE};
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::watch,
+    time::{Instant, Interval},
+};
+
+use crate::logging;
+
+/// how often config file is checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Reloads config whenever config file changes or SIGHUP is received. Never returns.
+///
+/// Settings read on every use (channels, log level, retry limits, ...) change live, database,
+/// irc login, sharding and health server address still need a restart.
+pub async fn run() {
+    let mut sighup = signal(SignalKind::hangup()).expect("couldn't listen for SIGHUP");
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    let mut last_modified = modified().await;
+
+    loop {
+        tokio::select! {
+            _ = sighup.recv() => info!("received SIGHUP, reloading config"),
+            _ = interval.tick() => {
+                let modified = modified().await;
+                if modified == last_modified {
+                    continue;
+                }
+
+                last_modified = modified;
+                info!("{CONFIG_FILE} changed, reloading config");
+            }
+        }
+
+        if let Err(err) = config::reload().await {
+            println!("Config wasn't reloaded: {err}");
+            error!("new config is invalid, keeping the old one: {err:?}");
+            continue;
+        }
+
+        logging::apply(&*get_config_async!().await);
+        info!("config reloaded");
+    }
+}
+
+async fn modified() -> Option<SystemTime> {
+    tokio::fs::metadata(CONFIG_FILE)
+        .await
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Interval whose period comes from config and follows reloads, e.g. for pollers
+pub struct ConfigInterval {
+    period_secs: fn(&Config) -> u64,
+    current_secs: u64,
+    interval: Interval,
+    config_updates: watch::Receiver<()>,
+}
+
+impl ConfigInterval {
+    /// First tick completes immediately, like with `tokio::time::interval`
+    pub async fn new(period_secs: fn(&Config) -> u64) -> Self {
+        let current_secs = period_secs(&*get_config_async!().await);
+
+        Self {
+            period_secs,
+            current_secs,
+            interval: tokio::time::interval(Duration::from_secs(current_secs)),
+            config_updates: config::subscribe(),
+        }
+    }
+
+    /// Waits for next tick, new period applies from the reload on and counts from it
+    pub async fn tick(&mut self) {
+        loop {
+            tokio::select! {
+                _ = self.interval.tick() => return,
+                Ok(()) = self.config_updates.changed() => {
+                    let period_secs = (self.period_secs)(&*get_config_async!().await);
+                    if period_secs == self.current_secs {
+                        continue;
+                    }
+
+                    debug!("interval changed from {}s to {period_secs}s", self.current_secs);
+
+                    let period = Duration::from_secs(period_secs);
+                    self.interval = tokio::time::interval_at(Instant::now() + period, period);
+                    self.current_secs = period_secs;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::{collections::HashSet, sync::Mutex};
+
+use chrono::{DateTime, Utc};
+use common::{
+    models::collection_gap::GapReason,
+    services::{channels, collection_gaps},
+};
+use diesel::PgConnection;
+use lazy_static::lazy_static;
+use twitch_irc::message::JoinMessage;
+
+use crate::{
+    config_reload::ConfigInterval,
+    metrics,
+    twitch_watcher::{DbPool, PooledConnection},
+};
+
+lazy_static! {
+    /// channels joined at least once since collector started, any later join is a rejoin
+    static ref JOINED_BEFORE: Mutex<HashSet<i32>> = Mutex::new(HashSet::new());
+    /// channels whose join was confirmed by twitch on current irc client
+    static ref RECORDING: Mutex<HashSet<i32>> = Mutex::new(HashSet::new());
+}
+
+/// Forgets confirmed joins, new irc client has to join everything again
+pub fn irc_client_started() {
+    RECORDING.lock().unwrap().clear();
+}
+
+/// Channel was parted on purpose, so it's recorded up to now and not anymore
+pub fn stop_recording(db_conn: &PgConnection, channel_id: i32) {
+    if !RECORDING.lock().unwrap().remove(&channel_id) {
+        return;
+    }
+
+    if let Err(err) = channels::mark_collected(db_conn, &[channel_id], Utc::now()) {
+        error!("couldn't record coverage of parted channel_id {channel_id}: {err:?}");
+    }
+}
+
+/// Periodically moves `last_collected_at` of recorded channels forward while irc is alive.
+/// Once irc comes back after a stall, the stall is recorded as a gap. Never returns.
+pub async fn run(pool: DbPool) {
+    let mut interval = ConfigInterval::new(|config| config.coverage_heartbeat_interval_secs).await;
+    let mut last_heartbeat = None;
+    let mut stalled = false;
+
+    loop {
+        interval.tick().await;
+
+        if !metrics::irc_alive() {
+            if last_heartbeat.is_some() && !stalled {
+                warn!("irc stalled, channels aren't being recorded");
+                stalled = true;
+            }
+            continue;
+        }
+
+        if let (true, Some(last_heartbeat)) = (stalled, last_heartbeat) {
+            resume(&pool, last_heartbeat);
+            stalled = false;
+        } else {
+            heartbeat(&pool);
+        }
+
+        last_heartbeat = Some(Utc::now());
+    }
+}
+
+/// Records that channels are being recorded right now
+pub fn heartbeat(pool: &DbPool) {
+    let channel_ids: Vec<i32> = RECORDING.lock().unwrap().iter().copied().collect();
+
+    if channel_ids.is_empty() {
+        return;
+    }
+
+    let Ok(db_conn) = pool.get() else {
+        error!("couldn't get connection to database from pool for coverage heartbeat");
+        return;
+    };
+
+    if let Err(err) = channels::mark_collected(&db_conn, &channel_ids, Utc::now()) {
+        error!("couldn't record coverage heartbeat: {err:?}");
+    }
+}
+
+/// Twitch confirmed we joined the channel, so everything since its last heartbeat is a gap
+pub fn handle_join(join: JoinMessage, db_conn: PooledConnection) {
+    let channel = match channels::get_channel_by_name(&db_conn, &join.channel_login) {
+        Ok(Some(channel)) => channel,
+        Ok(None) => {
+            error!("joined unknown channel {}", join.channel_login);
+            return;
+        }
+        Err(err) => {
+            error!("{err:?}");
+            return;
+        }
+    };
+
+    let reason = match JOINED_BEFORE.lock().unwrap().insert(channel.id) {
+        true => GapReason::CollectorDown,
+        false => GapReason::IrcDisconnected,
+    };
+
+    if let Err(err) = collection_gaps::resume_collecting(&db_conn, channel.id, reason, Utc::now()) {
+        error!(
+            "couldn't record collection gap of channel {}: {err:?}",
+            join.channel_login
+        );
+    }
+
+    RECORDING.lock().unwrap().insert(channel.id);
+}
+
+/// Records stall as a gap for channels that weren't rejoined (which records it too) since
+/// `last_heartbeat`
+fn resume(pool: &DbPool, last_heartbeat: DateTime<Utc>) {
+    let channel_ids: Vec<i32> = RECORDING.lock().unwrap().iter().copied().collect();
+
+    let Ok(db_conn) = pool.get() else {
+        error!("couldn't get connection to database from pool to record collection gaps");
+        return;
+    };
+
+    let now = Utc::now();
+
+    for channel_id in channel_ids {
+        let result = match channels::get_channel_by_id(&db_conn, channel_id) {
+            Ok(Some(channel)) if channel.last_collected_at > Some(last_heartbeat) => {
+                channels::mark_collected(&db_conn, &[channel_id], now).map(|_| ())
+            }
+            Ok(_) => collection_gaps::resume_collecting(
+                &db_conn,
+                channel_id,
+                GapReason::IrcDisconnected,
+                now,
+            ),
+            Err(err) => Err(err),
+        };
+
+        if let Err(err) = result {
+            error!("couldn't record collection gap of channel_id {channel_id}: {err:?}");
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::{convert::Infallible, time::Duration};
+
+use chrono::Utc;
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use prometheus::{Encoder, TextEncoder};
+
+use crate::{metrics, twitch_watcher::DbPool};
+
+const DB_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Serves `/healthz`, `/readyz` and `/metrics`. Returns only if server couldn't be started.
+pub async fn run(pool: DbPool) {
+    let address = get_config_async!().await.health_server_address;
+
+    let server = match Server::try_bind(&address) {
+        Ok(server) => server,
+        Err(err) => {
+            error!("couldn't start health server on {address}: {err}");
+            return;
+        }
+    };
+
+    let make_service = make_service_fn(move |_| {
+        let pool = pool.clone();
+
+        async move { Ok::<_, Infallible>(service_fn(move |request| handle(request, pool.clone()))) }
+    });
+
+    info!("health server listening on {address}");
+
+    if let Err(err) = server.serve(make_service).await {
+        error!("health server stopped: {err}");
+    }
+}
+
+async fn handle(request: Request<Body>, pool: DbPool) -> Result<Response<Body>, Infallible> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/healthz") => text(StatusCode::OK, "ok"),
+        (&Method::GET, "/readyz") => readiness(pool).await,
+        (&Method::GET, "/metrics") => Response::builder()
+            .header(CONTENT_TYPE, TextEncoder::new().format_type())
+            .body(Body::from(metrics::gather(&pool)))
+            .unwrap(),
+        _ => text(StatusCode::NOT_FOUND, "not found"),
+    };
+
+    Ok(response)
+}
+
+async fn readiness(pool: DbPool) -> Response<Body> {
+    if metrics::IRC_CONNECTED.get() != 1 {
+        return text(StatusCode::SERVICE_UNAVAILABLE, "irc not connected");
+    }
+
+    let last_message_age = Utc::now().timestamp() - metrics::IRC_LAST_MESSAGE.get();
+    if last_message_age > metrics::IRC_STALL_TIMEOUT_SECS {
+        return text(
+            StatusCode::SERVICE_UNAVAILABLE,
+            &format!("no message from irc for {last_message_age}s"),
+        );
+    }
+
+    let db_available =
+        tokio::task::spawn_blocking(move || pool.get_timeout(DB_CHECK_TIMEOUT).is_ok())
+            .await
+            .unwrap_or(false);
+
+    if !db_available {
+        return text(StatusCode::SERVICE_UNAVAILABLE, "database unavailable");
+    }
+
+    text(StatusCode::OK, "ready")
+}
+
+fn text(status: StatusCode, body: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::from(body.to_owned()))
+        .unwrap()
+}
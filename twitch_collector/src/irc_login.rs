@@ -0,0 +1,175 @@
+use std::fmt::{Debug, Display};
+
+use async_trait::async_trait;
+use common::{models::irc_token::NewIrcToken, services::irc_tokens};
+use error_stack::{IntoReport, Report, ResultExt};
+use twitch_irc::login::{TokenStorage, UserAccessToken};
+
+use crate::twitch_watcher::DbPool;
+
+#[derive(Debug)]
+pub enum TokenStorageError {
+    Io,
+    Serialization,
+    DbPool,
+    Database,
+    NoToken(String),
+}
+
+impl Display for TokenStorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenStorageError::Io => write!(f, "Couldn't access token file"),
+            TokenStorageError::Serialization => write!(f, "Token file has invalid format"),
+            TokenStorageError::DbPool => {
+                write!(f, "Couldn't get connection to database from pool")
+            }
+            TokenStorageError::Database => write!(f, "Database error"),
+            TokenStorageError::NoToken(login) => write!(
+                f,
+                "No irc token stored for \"{login}\", put initial token into token file"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TokenStorageError {}
+
+/// Keeps user access token in json file
+#[derive(Debug)]
+pub struct FileTokenStorage {
+    path: String,
+}
+
+impl FileTokenStorage {
+    pub fn new(path: String) -> Self {
+        Self { path }
+    }
+}
+
+#[async_trait]
+impl TokenStorage for FileTokenStorage {
+    type LoadError = Report<TokenStorageError>;
+    type UpdateError = Report<TokenStorageError>;
+
+    async fn load_token(&mut self) -> Result<UserAccessToken, Self::LoadError> {
+        read_token_file(&self.path).await
+    }
+
+    async fn update_token(&mut self, token: &UserAccessToken) -> Result<(), Self::UpdateError> {
+        let json = serde_json::to_vec_pretty(token)
+            .into_report()
+            .change_context(TokenStorageError::Serialization)?;
+
+        tokio::fs::write(&self.path, json)
+            .await
+            .into_report()
+            .change_context(TokenStorageError::Io)
+            .attach_printable_lazy(|| format!("token file: {}", self.path))
+    }
+}
+
+/// Keeps user access token in `irc_tokens` table.
+///
+/// If there is no token for `login` yet, initial one is imported from `seed_file`.
+pub struct DatabaseTokenStorage {
+    pool: DbPool,
+    login: String,
+    seed_file: String,
+}
+
+impl DatabaseTokenStorage {
+    pub fn new(pool: DbPool, login: String, seed_file: String) -> Self {
+        Self {
+            pool,
+            login,
+            seed_file,
+        }
+    }
+}
+
+// PgConnection isn't Debug, so pool can't be printed
+impl Debug for DatabaseTokenStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DatabaseTokenStorage")
+            .field("login", &self.login)
+            .field("seed_file", &self.seed_file)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl TokenStorage for DatabaseTokenStorage {
+    type LoadError = Report<TokenStorageError>;
+    type UpdateError = Report<TokenStorageError>;
+
+    async fn load_token(&mut self) -> Result<UserAccessToken, Self::LoadError> {
+        let irc_token = {
+            let db_conn = self
+                .pool
+                .get()
+                .into_report()
+                .change_context(TokenStorageError::DbPool)?;
+
+            irc_tokens::get_by_login(&db_conn, &self.login)
+                .change_context(TokenStorageError::Database)?
+        };
+
+        if let Some(irc_token) = irc_token {
+            return Ok(UserAccessToken {
+                access_token: irc_token.access_token,
+                refresh_token: irc_token.refresh_token,
+                created_at: irc_token.created_at,
+                expires_at: irc_token.expires_at,
+            });
+        }
+
+        info!(
+            "no irc token of {} in database, importing it from {}",
+            self.login, self.seed_file
+        );
+
+        let token = read_token_file(&self.seed_file)
+            .await
+            .change_context_lazy(|| TokenStorageError::NoToken(self.login.clone()))?;
+
+        self.update_token(&token).await?;
+
+        Ok(token)
+    }
+
+    async fn update_token(&mut self, token: &UserAccessToken) -> Result<(), Self::UpdateError> {
+        let db_conn = self
+            .pool
+            .get()
+            .into_report()
+            .change_context(TokenStorageError::DbPool)?;
+
+        irc_tokens::upsert(
+            &db_conn,
+            NewIrcToken {
+                login: self.login.clone(),
+                access_token: token.access_token.clone(),
+                refresh_token: token.refresh_token.clone(),
+                created_at: token.created_at,
+                expires_at: token.expires_at,
+            },
+        )
+        .change_context(TokenStorageError::Database)?;
+
+        Ok(())
+    }
+}
+
+async fn read_token_file(path: &str) -> Result<UserAccessToken, Report<TokenStorageError>> {
+    let json = tokio::fs::read(path)
+        .await
+        .into_report()
+        .change_context(TokenStorageError::Io)
+        .attach_printable_lazy(|| format!("token file: {path}"))?;
+
+    serde_json::from_slice(&json)
+        .into_report()
+        .change_context(TokenStorageError::Serialization)
+        .attach_printable_lazy(|| format!("token file: {path}"))
+}
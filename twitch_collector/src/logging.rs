@@ -0,0 +1,511 @@
+use std::{
+    cell::RefCell,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, RwLock,
+    },
+};
+
+use chrono::{SecondsFormat, Utc};
+use common::config::{Config, LogFormat, LoggingConfig};
+use error_stack::{AttachmentKind, FrameKind, Report};
+use lazy_static::lazy_static;
+use log::{LevelFilter, Log, Metadata, Record};
+use serde_json::{Map, Value};
+
+/// Logs with structured fields which become keys in json output and `key=value` in text.
+///
+/// `log_with!(Error, [channel = login, user = sender], "couldn't save message: {err}")`
+macro_rules! log_with {
+    ($level:ident, [$($key:ident = $value:expr),* $(,)?], $($arg:tt)+) => {
+        $crate::logging::with_fields(
+            &[$((stringify!($key), $value.to_string())),*],
+            || log::log!(log::Level::$level, $($arg)+),
+        )
+    };
+}
+
+lazy_static! {
+    static ref LEVELS: RwLock<Levels> = RwLock::new(Levels {
+        default: LevelFilter::Info,
+        modules: vec![],
+    });
+}
+
+static MESSAGE_SAMPLE_RATE: AtomicU64 = AtomicU64::new(0);
+static MESSAGES_SEEN: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// fields of the record being logged by `log_with!` on this thread
+    static FIELDS: RefCell<Vec<(&'static str, String)>> = const { RefCell::new(vec![]) };
+}
+
+struct Levels {
+    default: LevelFilter,
+    /// longest module first, so the most specific one wins
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Levels {
+    fn new(default: LevelFilter, mut modules: Vec<(String, LevelFilter)>) -> Self {
+        modules.sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+
+        Self { default, modules }
+    }
+
+    fn for_target(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .find(|(module, _)| {
+                target == module
+                    || (target.starts_with(module.as_str())
+                        && target[module.len()..].starts_with("::"))
+            })
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+
+    fn max(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, |max, level| max.max(level))
+    }
+}
+
+struct Logger {
+    stdout: Option<LogFormat>,
+    file: Option<(LogFormat, Mutex<RotatingFile>)>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= LEVELS.read().unwrap().for_target(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        FIELDS.with(|fields| {
+            let fields = fields.borrow();
+
+            if let Some(format) = self.stdout {
+                println!("{}", format_record(format, record, &fields));
+            }
+
+            if let Some((format, file)) = &self.file {
+                let line = format_record(*format, record, &fields);
+
+                if let Err(err) = file.lock().unwrap().write_line(&line) {
+                    eprintln!("couldn't write log file: {err}");
+                }
+            }
+        });
+    }
+
+    fn flush(&self) {
+        if let Some((_, file)) = &self.file {
+            let _ = file.lock().unwrap().file.flush();
+        }
+    }
+}
+
+/// Sets up sinks from config, they can't be changed without restart unlike levels
+pub fn init(config: &Config) -> io::Result<()> {
+    let file = match &config.logging.file {
+        Some(file) => Some((
+            file.format,
+            Mutex::new(RotatingFile::open(
+                &file.path,
+                file.max_size_mb * 1024 * 1024,
+                file.keep,
+            )?),
+        )),
+        None => None,
+    };
+
+    let logger = Logger {
+        stdout: config
+            .logging
+            .stdout
+            .then_some(config.logging.stdout_format),
+        file,
+    };
+
+    log::set_boxed_logger(Box::new(logger)).map_err(io::Error::other)?;
+    apply(config);
+
+    Ok(())
+}
+
+/// Applies levels and message sampling from config, called again on every config reload
+pub fn apply(config: &Config) {
+    let LoggingConfig {
+        modules,
+        message_sample_rate,
+        ..
+    } = &config.logging;
+
+    let levels = Levels::new(
+        config.log_level.into(),
+        modules
+            .iter()
+            .map(|(module, level)| (module.clone(), (*level).into()))
+            .collect(),
+    );
+
+    log::set_max_level(levels.max());
+    *LEVELS.write().unwrap() = levels;
+    MESSAGE_SAMPLE_RATE.store(*message_sample_rate, Ordering::Relaxed);
+}
+
+/// Whether this received irc message should be logged, so busy channels don't flood the log
+pub fn sample_message() -> bool {
+    let rate = MESSAGE_SAMPLE_RATE.load(Ordering::Relaxed);
+
+    rate != 0
+        && MESSAGES_SEEN
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(rate)
+}
+
+/// Runs `log` with `fields` attached to records it logs, use `log_with!` instead
+pub fn with_fields(fields: &[(&'static str, String)], log: impl FnOnce()) {
+    let outer = FIELDS.with(|current| {
+        let mut current = current.borrow_mut();
+        let outer = current.len();
+        current.extend_from_slice(fields);
+
+        outer
+    });
+    log();
+    // fields of outer `log_with!` stay for the rest of it
+    FIELDS.with(|current| current.borrow_mut().truncate(outer));
+}
+
+/// Contexts and printable attachments of `report` from the newest one, for the `error` field
+pub fn error_chain<C>(report: &Report<C>) -> String {
+    report
+        .frames()
+        .filter_map(|frame| match frame.kind() {
+            FrameKind::Context(context) => Some(context.to_string()),
+            FrameKind::Attachment(AttachmentKind::Printable(attachment)) => {
+                Some(attachment.to_string())
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+fn format_record(format: LogFormat, record: &Record, fields: &[(&'static str, String)]) -> String {
+    let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+
+    match format {
+        LogFormat::Text => {
+            let mut line = format!(
+                "{timestamp} {:<5} {}: {}",
+                record.level(),
+                record.target(),
+                record.args()
+            );
+
+            for (key, value) in fields {
+                line.push_str(&format!(" {key}={value:?}"));
+            }
+
+            line
+        }
+        LogFormat::Json => {
+            let mut object = Map::new();
+            object.insert("timestamp".to_owned(), Value::from(timestamp));
+            object.insert("level".to_owned(), Value::from(record.level().as_str()));
+            object.insert("target".to_owned(), Value::from(record.target()));
+            object.insert("message".to_owned(), Value::from(record.args().to_string()));
+
+            // field named like one of the keys above is kept as `fields.<key>`
+            for (key, value) in fields {
+                let key = if object.contains_key(*key) {
+                    format!("fields.{key}")
+                } else {
+                    key.to_string()
+                };
+
+                object.insert(key, Value::from(value.as_str()));
+            }
+
+            Value::Object(object).to_string()
+        }
+    }
+}
+
+/// Log file that's moved to `<path>.1` once it's full, older files move one number up
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    keep: u32,
+}
+
+impl RotatingFile {
+    fn open(path: &str, max_size: u64, keep: u32) -> io::Result<Self> {
+        let path = PathBuf::from(path);
+
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_size,
+            keep,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let line_size = line.len() as u64 + 1;
+
+        if self.size > 0 && self.size + line_size > self.max_size {
+            self.rotate()?;
+        }
+
+        writeln!(self.file, "{line}")?;
+        self.size += line_size;
+
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        // the oldest one is overwritten by the one before it
+        for index in (1..self.keep).rev() {
+            let from = self.rotated(index);
+
+            if from.exists() {
+                fs::rename(from, self.rotated(index + 1))?;
+            }
+        }
+
+        if self.keep > 0 {
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+
+        Ok(())
+    }
+
+    fn rotated(&self, index: u32) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+
+        PathBuf::from(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn levels() -> Levels {
+        Levels::new(
+            LevelFilter::Info,
+            vec![
+                ("a::b".to_owned(), LevelFilter::Warn),
+                ("a::b::c".to_owned(), LevelFilter::Trace),
+                ("a".to_owned(), LevelFilter::Debug),
+            ],
+        )
+    }
+
+    #[test]
+    fn level_of_module_applies_to_its_submodules() {
+        assert_eq!(levels().for_target("a::b"), LevelFilter::Warn);
+        assert_eq!(levels().for_target("a::b::d"), LevelFilter::Warn);
+    }
+
+    #[test]
+    fn level_of_module_does_not_apply_to_module_with_same_prefix() {
+        assert_eq!(levels().for_target("a::bc"), LevelFilter::Debug);
+        assert_eq!(levels().for_target("ab"), LevelFilter::Info);
+    }
+
+    #[test]
+    fn most_specific_module_wins() {
+        assert_eq!(levels().for_target("a::b::c::d"), LevelFilter::Trace);
+        assert_eq!(levels().for_target("a::x"), LevelFilter::Debug);
+    }
+
+    #[test]
+    fn max_level_covers_every_module() {
+        assert_eq!(levels().max(), LevelFilter::Trace);
+    }
+
+    fn current_fields() -> Vec<(&'static str, String)> {
+        FIELDS.with(|fields| fields.borrow().clone())
+    }
+
+    #[test]
+    fn nested_fields_keep_outer_ones() {
+        with_fields(&[("channel", "a".to_owned())], || {
+            with_fields(&[("user", "b".to_owned())], || {
+                assert_eq!(
+                    current_fields(),
+                    vec![("channel", "a".to_owned()), ("user", "b".to_owned())]
+                );
+            });
+
+            assert_eq!(current_fields(), vec![("channel", "a".to_owned())]);
+        });
+
+        assert!(current_fields().is_empty());
+    }
+
+    fn json_record(fields: &[(&'static str, String)]) -> Value {
+        let line = format_record(
+            LogFormat::Json,
+            &Record::builder()
+                .args(format_args!("couldn't save message"))
+                .level(Level::Error)
+                .target("twitch_collector::twitch_watcher")
+                .build(),
+            fields,
+        );
+
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[test]
+    fn json_record_has_fields_as_keys() {
+        let record = json_record(&[("channel", "a".to_owned())]);
+
+        assert_eq!(record["level"], "ERROR");
+        assert_eq!(record["target"], "twitch_collector::twitch_watcher");
+        assert_eq!(record["message"], "couldn't save message");
+        assert_eq!(record["channel"], "a");
+    }
+
+    #[test]
+    fn json_field_named_like_builtin_key_is_kept_apart() {
+        let record = json_record(&[("message", "hello".to_owned()), ("level", "x".to_owned())]);
+
+        assert_eq!(record["message"], "couldn't save message");
+        assert_eq!(record["level"], "ERROR");
+        assert_eq!(record["fields.message"], "hello");
+        assert_eq!(record["fields.level"], "x");
+    }
+
+    #[test]
+    fn text_record_has_fields_at_the_end() {
+        let line = format_record(
+            LogFormat::Text,
+            &Record::builder()
+                .args(format_args!("joined"))
+                .level(Level::Info)
+                .target("twitch_collector")
+                .build(),
+            &[("channel", "a".to_owned())],
+        );
+
+        assert!(line.ends_with(" INFO  twitch_collector: joined channel=\"a\""));
+    }
+
+    #[test]
+    fn messages_are_sampled_at_rate() {
+        MESSAGE_SAMPLE_RATE.store(0, Ordering::Relaxed);
+        assert!((0..10).all(|_| !sample_message()));
+
+        MESSAGE_SAMPLE_RATE.store(3, Ordering::Relaxed);
+        assert_eq!((0..9).filter(|_| sample_message()).count(), 3);
+
+        MESSAGE_SAMPLE_RATE.store(1, Ordering::Relaxed);
+        assert!((0..10).all(|_| sample_message()));
+    }
+
+    /// Writes `lines` to log file in `dir` that's full after every line
+    fn write_lines(dir: &TempDir, keep: u32, lines: &[&str]) -> PathBuf {
+        let path = dir.path().join("collector.log");
+        let mut file = RotatingFile::open(path.to_str().unwrap(), 4, keep).unwrap();
+
+        for line in lines {
+            file.write_line(line).unwrap();
+        }
+
+        path
+    }
+
+    fn read(path: PathBuf) -> Option<String> {
+        fs::read_to_string(path).ok()
+    }
+
+    #[test]
+    fn rotation_without_kept_files_truncates_log() {
+        let dir = TempDir::new().unwrap();
+        let path = write_lines(&dir, 0, &["one", "two"]);
+
+        assert_eq!(read(path.clone()).as_deref(), Some("two\n"));
+        assert_eq!(read(path.with_extension("log.1")), None);
+    }
+
+    #[test]
+    fn rotation_keeps_single_previous_file() {
+        let dir = TempDir::new().unwrap();
+        let path = write_lines(&dir, 1, &["one", "two", "three"]);
+
+        assert_eq!(read(path.clone()).as_deref(), Some("three\n"));
+        assert_eq!(read(path.with_extension("log.1")).as_deref(), Some("two\n"));
+        assert_eq!(read(path.with_extension("log.2")), None);
+    }
+
+    #[test]
+    fn rotation_moves_older_files_up_and_drops_oldest() {
+        let dir = TempDir::new().unwrap();
+        let path = write_lines(&dir, 3, &["one", "two", "three", "four", "five"]);
+
+        assert_eq!(read(path.clone()).as_deref(), Some("five\n"));
+        assert_eq!(
+            read(path.with_extension("log.1")).as_deref(),
+            Some("four\n")
+        );
+        assert_eq!(
+            read(path.with_extension("log.2")).as_deref(),
+            Some("three\n")
+        );
+        assert_eq!(read(path.with_extension("log.3")).as_deref(), Some("two\n"));
+        assert_eq!(read(path.with_extension("log.4")), None);
+    }
+
+    #[test]
+    fn lines_are_appended_until_file_is_full() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("collector.log");
+        let mut file = RotatingFile::open(path.to_str().unwrap(), 1024, 1).unwrap();
+
+        file.write_line("one").unwrap();
+        file.write_line("two").unwrap();
+
+        assert_eq!(read(path.clone()).as_deref(), Some("one\ntwo\n"));
+        assert_eq!(read(path.with_extension("log.1")), None);
+    }
+}
@@ -1,11 +1,10 @@
-#![feature(proc_macro_hygiene, decl_macro)]
-
 use std::process::exit;
 
 use diesel::r2d2::{ConnectionManager, Pool};
 
-use common::config;
+use common::{config, config_check, models::user_erasure::ErasureMode, services::users};
 use twitch_api2::HelixClient;
+use twitch_watcher::RunError;
 
 #[macro_use]
 extern crate log;
@@ -13,23 +12,54 @@ extern crate log;
 #[macro_use]
 extern crate common;
 
+mod channel_reconciler;
+mod cold_archive;
+mod config_channels;
+mod config_reload;
+mod coverage;
+mod health_server;
+mod irc_login;
+#[macro_use]
+mod logging;
+mod metrics;
+mod parquet;
+mod partitions;
+mod retention;
+mod s3;
+mod sharding;
+mod shutdown;
+mod stream_poller;
+mod supervisor;
+mod token_manager;
 mod twitch_watcher;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let _ = dotenvy::dotenv();
     if let Err(err) = config::load().await {
-        println!("{}", err);
+        println!("{:?}", err);
         exit(1);
     }
 
-    let config = get_config_async!().await;
+    if std::env::args().any(|arg| arg == "--check-config") {
+        match config_check::check_connectivity().await {
+            Ok(()) => {
+                println!("Config is valid, database and twitch api are reachable");
+                exit(0);
+            }
+            Err(err) => {
+                println!("{:?}", err);
+                exit(1);
+            }
+        }
+    }
 
-    // TODO log to file
-    env_logger::builder()
-        .target(env_logger::Target::Stdout)
-        .filter_level(config.log_level.into())
-        .init();
+    // guard isn't kept around, config reload needs write access
+    let logging = logging::init(&*get_config_async!().await);
+    if let Err(err) = logging {
+        println!("Couldn't set up logging: {err}");
+        exit(1);
+    }
 
     debug!("debug works");
     info!("info works");
@@ -39,14 +69,144 @@ async fn main() -> anyhow::Result<()> {
 
     // let db_conn = PgConnection::establish(&get_config_async!().await.database.url.clone())?;
     let conn_manager = ConnectionManager::new(common::construct_db_url_async().await);
-    let pool = Pool::new(conn_manager).expect("error while creating db pool");
+    let pool = match Pool::new(conn_manager) {
+        Ok(pool) => pool,
+        Err(err) => {
+            println!("Couldn't connect to database: {err}. Run with --check-config for details");
+            error!("couldn't create db pool: {err}");
+            exit(1);
+        }
+    };
+
+    if std::env::args().any(|arg| arg == "--prune") {
+        match retention::prune(pool).await {
+            Ok(run) => {
+                for report in &run.reports {
+                    println!(
+                        "{}: {} message(s), {} resub(s)",
+                        report.channel_name, report.messages, report.resubs
+                    );
+                }
+                println!("orphaned resubs: {}", run.orphaned_resubs);
+
+                if run.skipped {
+                    println!("Other instance is pruning right now, try again later");
+                    exit(1);
+                }
+                if !run.failed_channels.is_empty() {
+                    println!(
+                        "Couldn't prune channels: {}. Check logs for details",
+                        run.failed_channels.join(", ")
+                    );
+                    exit(1);
+                }
+                exit(0);
+            }
+            Err(err) => {
+                println!("{:?}", err);
+                exit(1);
+            }
+        }
+    }
+
+    if std::env::args().any(|arg| arg == "--cold-archive") {
+        match cold_archive::run(pool).await {
+            Ok(partitions) => {
+                for partition in &partitions {
+                    println!(
+                        "{}: {} message(s) in {} file(s), {} resub(s)",
+                        partition.month.format("%Y-%m"),
+                        partition.messages,
+                        partition.files,
+                        partition.resubs
+                    );
+                }
+                println!("archived partitions: {}", partitions.len());
+                exit(0);
+            }
+            Err(err) => {
+                println!("{:?}", err);
+                exit(1);
+            }
+        }
+    }
+
+    if let Some(twitch_user_id) = arg_value("--erase-user") {
+        let Some(requested_by) = arg_value("--requested-by") else {
+            println!("--erase-user needs --requested-by <who asked for erasure>");
+            exit(1);
+        };
+        let mode = match std::env::args().any(|arg| arg == "--anonymize") {
+            true => ErasureMode::Anonymize,
+            false => ErasureMode::Delete,
+        };
+        let reason = arg_value("--reason");
 
-    // run only returns in case of an error
-    let run = twitch_watcher::run(pool.clone(), twitch_api_client).await.unwrap_err();
+        let user_id = twitch_user_id.clone();
+        let erasure = tokio::task::spawn_blocking(move || {
+            let db_conn = pool.get()?;
 
-    println!("{}", run);
-    error!("{:?}", run);
+            users::erase_user(&db_conn, &user_id, mode, &requested_by, reason.as_deref())
+                .map_err(|err| anyhow::anyhow!("{err:?}"))
+        })
+        .await?;
+
+        match erasure {
+            Ok(Some(erasure)) => {
+                println!(
+                    "Erased user {}: {} message(s) {}, {} old name(s) deleted, audit record {}",
+                    erasure.twitch_user_id,
+                    erasure.messages,
+                    match erasure.mode {
+                        ErasureMode::Delete => "deleted",
+                        ErasureMode::Anonymize => "anonymized",
+                    },
+                    erasure.old_names,
+                    erasure.uuid
+                );
+                println!(
+                    "Files in retention and cold archive still contain the user, if there are any, \
+                     audit record keeps archives_processed false until they're scrubbed"
+                );
+                exit(0);
+            }
+            Ok(None) => {
+                println!("There's no user with twitch user id {}", twitch_user_id);
+                exit(1);
+            }
+            Err(err) => {
+                println!("{err}");
+                exit(1);
+            }
+        }
+    }
+
+    tokio::spawn(health_server::run(pool.clone()));
+    tokio::spawn(partitions::run(pool.clone()));
+    tokio::spawn(retention::run(pool.clone()));
+    tokio::spawn(config_reload::run());
+
+    let shutdown = shutdown::listen();
+
+    // supervisor restarts irc client on errors and returns only after shutdown
+    if let Err(run) = supervisor::run(pool.clone(), twitch_api_client, shutdown).await {
+        println!("{}", run);
+        error!("{:?}", run);
+
+        let exit_code = match run.current_context() {
+            RunError::ShutdownTimeout => 2,
+            _ => 1,
+        };
+
+        info!("exiting with code {exit_code}");
+        exit(exit_code);
+    }
 
     info!("exiting");
     Ok(())
 }
+
+/// Value that follows `name` in command line arguments, e.g. `--reason "asked by email"`
+fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
+}
@@ -0,0 +1,128 @@
+use chrono::Utc;
+use lazy_static::lazy_static;
+use prometheus::{
+    register_histogram, register_int_counter, register_int_counter_vec, register_int_gauge,
+    Encoder, Histogram, IntCounter, IntCounterVec, IntGauge, TextEncoder,
+};
+use twitch_irc::message::ServerMessage;
+
+use crate::twitch_watcher::DbPool;
+
+/// twitch pings every ~5 minutes, so no message for longer than this means irc is stuck
+pub const IRC_STALL_TIMEOUT_SECS: i64 = 6 * 60;
+
+lazy_static! {
+    pub static ref MESSAGES_RECEIVED: IntCounterVec = register_int_counter_vec!(
+        "collector_messages_received_total",
+        "Chat messages received from irc",
+        &["channel"]
+    )
+    .unwrap();
+    pub static ref MESSAGES_STORED: IntCounterVec = register_int_counter_vec!(
+        "collector_messages_stored_total",
+        "Chat messages saved to database",
+        &["channel"]
+    )
+    .unwrap();
+    pub static ref MESSAGES_DROPPED: IntCounterVec = register_int_counter_vec!(
+        "collector_messages_dropped_total",
+        "Chat messages that couldn't be saved",
+        &["channel", "reason"]
+    )
+    .unwrap();
+    pub static ref JOIN_FAILURES: IntCounterVec = register_int_counter_vec!(
+        "collector_join_failures_total",
+        "Failed attempts to join channel",
+        &["channel"]
+    )
+    .unwrap();
+    pub static ref MESSAGES_PRUNED: IntCounterVec = register_int_counter_vec!(
+        "collector_messages_pruned_total",
+        "Chat messages deleted because they're older than retention of their channel",
+        &["channel"]
+    )
+    .unwrap();
+    pub static ref PARTITION_FAILURES: IntCounter = register_int_counter!(
+        "collector_partition_failures_total",
+        "Failed attempts to create messages partitions ahead of time"
+    )
+    .unwrap();
+    pub static ref INSERT_DURATION: Histogram = register_histogram!(
+        "collector_message_insert_duration_seconds",
+        "Time it takes to save single chat message"
+    )
+    .unwrap();
+    pub static ref IRC_CONNECTED: IntGauge =
+        register_int_gauge!("collector_irc_connected", "1 while irc connection is up").unwrap();
+    pub static ref IRC_LAST_MESSAGE: IntGauge = register_int_gauge!(
+        "collector_irc_last_message_timestamp_seconds",
+        "Unix time of the last message received from irc, including pings"
+    )
+    .unwrap();
+    static ref DB_POOL_CONNECTIONS: IntGauge = register_int_gauge!(
+        "collector_db_pool_connections",
+        "Connections managed by database pool"
+    )
+    .unwrap();
+    static ref DB_POOL_IDLE_CONNECTIONS: IntGauge = register_int_gauge!(
+        "collector_db_pool_idle_connections",
+        "Idle connections in database pool"
+    )
+    .unwrap();
+    static ref DB_POOL_MAX_CONNECTIONS: IntGauge = register_int_gauge!(
+        "collector_db_pool_max_connections",
+        "Maximum size of database pool"
+    )
+    .unwrap();
+}
+
+/// Updates irc connection state from message that just came in
+pub fn observe_irc_message(message: &ServerMessage) {
+    IRC_LAST_MESSAGE.set(Utc::now().timestamp());
+
+    match message {
+        ServerMessage::Reconnect(_) => IRC_CONNECTED.set(0),
+        _ => IRC_CONNECTED.set(1),
+    }
+}
+
+/// Irc is connected and messages (or at least pings) keep coming
+pub fn irc_alive() -> bool {
+    IRC_CONNECTED.get() == 1
+        && Utc::now().timestamp() - IRC_LAST_MESSAGE.get() <= IRC_STALL_TIMEOUT_SECS
+}
+
+pub fn message_stored(channel: &str) {
+    MESSAGES_STORED.with_label_values(&[channel]).inc();
+}
+
+pub fn message_dropped(channel: &str, reason: &str) {
+    MESSAGES_DROPPED.with_label_values(&[channel, reason]).inc();
+}
+
+/// Channel login of messages that are stored, `None` for everything else
+pub fn message_channel(message: &ServerMessage) -> Option<&str> {
+    match message {
+        ServerMessage::Privmsg(msg) => Some(&msg.channel_login),
+        ServerMessage::UserNotice(msg) => Some(&msg.channel_login),
+        ServerMessage::RoomState(msg) => Some(&msg.channel_login),
+        _ => None,
+    }
+}
+
+/// Returns all metrics in prometheus text format
+pub fn gather(pool: &DbPool) -> Vec<u8> {
+    let state = pool.state();
+    DB_POOL_CONNECTIONS.set(state.connections.into());
+    DB_POOL_IDLE_CONNECTIONS.set(state.idle_connections.into());
+    DB_POOL_MAX_CONNECTIONS.set(pool.max_size().into());
+
+    let mut buffer = vec![];
+    let encoder = TextEncoder::new();
+
+    if let Err(err) = encoder.encode(&prometheus::gather(), &mut buffer) {
+        error!("couldn't encode metrics: {err}");
+    }
+
+    buffer
+}
@@ -0,0 +1,261 @@
+// Writer of flat, uncompressed parquet files with a single row group. Columns are plain optional
+// values, so they're written with column writers of `parquet` crate and its default features,
+// which pull in arrow and compression codecs, stay off.
+
+use std::sync::Arc;
+
+use parquet::{
+    basic::{LogicalType, Repetition, TimeUnit, Type as PhysicalType},
+    data_type::{ByteArray, ByteArrayType, Int32Type, Int64Type},
+    errors::Result,
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    format::MicroSeconds,
+    schema::types::Type,
+};
+
+/// Values of one column, every column is optional so `None` is null
+pub enum Values {
+    Int16(Vec<Option<i16>>),
+    Int32(Vec<Option<i32>>),
+    Int64(Vec<Option<i64>>),
+    /// microseconds since unix epoch in UTC
+    Timestamp(Vec<Option<i64>>),
+    Utf8(Vec<Option<String>>),
+}
+
+impl Values {
+    fn len(&self) -> usize {
+        match self {
+            Values::Int16(values) => values.len(),
+            Values::Int32(values) => values.len(),
+            Values::Int64(values) | Values::Timestamp(values) => values.len(),
+            Values::Utf8(values) => values.len(),
+        }
+    }
+
+    fn physical_type(&self) -> PhysicalType {
+        match self {
+            Values::Int16(_) | Values::Int32(_) => PhysicalType::INT32,
+            Values::Int64(_) | Values::Timestamp(_) => PhysicalType::INT64,
+            Values::Utf8(_) => PhysicalType::BYTE_ARRAY,
+        }
+    }
+
+    fn logical_type(&self) -> Option<LogicalType> {
+        match self {
+            Values::Int16(_) => Some(LogicalType::Integer {
+                bit_width: 16,
+                is_signed: true,
+            }),
+            Values::Timestamp(_) => Some(LogicalType::Timestamp {
+                is_adjusted_to_u_t_c: true,
+                unit: TimeUnit::MICROS(MicroSeconds {}),
+            }),
+            Values::Utf8(_) => Some(LogicalType::String),
+            Values::Int32(_) | Values::Int64(_) => None,
+        }
+    }
+
+    /// Definition levels, 1 for value and 0 for null
+    fn definition_levels(&self) -> Vec<i16> {
+        fn levels<T>(values: &[Option<T>]) -> Vec<i16> {
+            values.iter().map(|value| value.is_some() as i16).collect()
+        }
+
+        match self {
+            Values::Int16(values) => levels(values),
+            Values::Int32(values) => levels(values),
+            Values::Int64(values) | Values::Timestamp(values) => levels(values),
+            Values::Utf8(values) => levels(values),
+        }
+    }
+}
+
+pub struct Column {
+    pub name: &'static str,
+    pub values: Values,
+}
+
+impl Column {
+    fn field(&self) -> Result<Type> {
+        Type::primitive_type_builder(self.name, self.values.physical_type())
+            .with_repetition(Repetition::OPTIONAL)
+            .with_logical_type(self.values.logical_type())
+            .build()
+    }
+}
+
+/// Parquet file with `columns` as its only row group, columns have to be equally long
+pub fn write(columns: &[Column]) -> Result<Vec<u8>> {
+    let num_rows = columns
+        .first()
+        .map(|column| column.values.len())
+        .unwrap_or(0);
+    assert!(
+        columns.iter().all(|column| column.values.len() == num_rows),
+        "columns of parquet file have different lengths"
+    );
+
+    let fields = columns
+        .iter()
+        .map(|column| column.field().map(Arc::new))
+        .collect::<Result<_>>()?;
+    let schema = Type::group_type_builder("schema")
+        .with_fields(fields)
+        .build()?;
+
+    let mut out = vec![];
+    let mut writer = SerializedFileWriter::new(
+        &mut out,
+        Arc::new(schema),
+        Arc::new(WriterProperties::builder().build()),
+    )?;
+    let mut row_group = writer.next_row_group()?;
+
+    for column in columns {
+        let mut column_writer = row_group.next_column()?.expect("every column is in schema");
+        let levels = column.values.definition_levels();
+        let levels = Some(levels.as_slice());
+
+        match &column.values {
+            Values::Int16(values) => {
+                let values: Vec<i32> = values.iter().flatten().map(|&value| value.into()).collect();
+                column_writer
+                    .typed::<Int32Type>()
+                    .write_batch(&values, levels, None)?;
+            }
+            Values::Int32(values) => {
+                let values: Vec<i32> = values.iter().flatten().copied().collect();
+                column_writer
+                    .typed::<Int32Type>()
+                    .write_batch(&values, levels, None)?;
+            }
+            Values::Int64(values) | Values::Timestamp(values) => {
+                let values: Vec<i64> = values.iter().flatten().copied().collect();
+                column_writer
+                    .typed::<Int64Type>()
+                    .write_batch(&values, levels, None)?;
+            }
+            Values::Utf8(values) => {
+                let values: Vec<ByteArray> = values
+                    .iter()
+                    .flatten()
+                    .map(|value| value.as_str().into())
+                    .collect();
+                column_writer
+                    .typed::<ByteArrayType>()
+                    .write_batch(&values, levels, None)?;
+            }
+        }
+
+        column_writer.close()?;
+    }
+
+    row_group.close()?;
+    writer.close()?;
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+
+    use parquet::{
+        file::reader::{FileReader, SerializedFileReader},
+        record::Field,
+    };
+
+    use super::*;
+
+    /// Reads `file` with reader of `parquet` crate, `name` keeps files of tests apart
+    fn read_back(
+        name: &str,
+        file: Vec<u8>,
+    ) -> (SerializedFileReader<File>, Vec<Vec<(String, Field)>>) {
+        let path = std::env::temp_dir().join(format!("{name}-{}.parquet", std::process::id()));
+        fs::write(&path, file).unwrap();
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let rows = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| {
+                row.unwrap()
+                    .get_column_iter()
+                    .map(|(name, field)| (name.clone(), field.clone()))
+                    .collect()
+            })
+            .collect();
+
+        (reader, rows)
+    }
+
+    #[test]
+    fn written_file_reads_back() {
+        let file = write(&[
+            Column {
+                name: "id",
+                values: Values::Int64(vec![Some(1), Some(2)]),
+            },
+            Column {
+                name: "send_time",
+                values: Values::Timestamp(vec![Some(1_674_324_000_000_000), None]),
+            },
+            Column {
+                name: "msg",
+                values: Values::Utf8(vec![None, Some("ü kappa".to_owned())]),
+            },
+            Column {
+                name: "stream_session_id",
+                values: Values::Int32(vec![Some(7), None]),
+            },
+            Column {
+                name: "resub_tier",
+                values: Values::Int16(vec![None, Some(3)]),
+            },
+        ])
+        .unwrap();
+
+        let (reader, rows) = read_back("written_file_reads_back", file);
+
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 2);
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    ("id".to_owned(), Field::Long(1)),
+                    (
+                        "send_time".to_owned(),
+                        Field::TimestampMicros(1_674_324_000_000_000)
+                    ),
+                    ("msg".to_owned(), Field::Null),
+                    ("stream_session_id".to_owned(), Field::Int(7)),
+                    ("resub_tier".to_owned(), Field::Null),
+                ],
+                vec![
+                    ("id".to_owned(), Field::Long(2)),
+                    ("send_time".to_owned(), Field::Null),
+                    ("msg".to_owned(), Field::Str("ü kappa".to_owned())),
+                    ("stream_session_id".to_owned(), Field::Null),
+                    ("resub_tier".to_owned(), Field::Short(3)),
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn file_without_rows_reads_back() {
+        let file = write(&[Column {
+            name: "id",
+            values: Values::Int64(vec![]),
+        }])
+        .unwrap();
+
+        let (reader, rows) = read_back("file_without_rows_reads_back", file);
+
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 0);
+        assert!(rows.is_empty());
+    }
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use common::services::messages;
+
+use crate::{metrics, twitch_watcher::DbPool};
+
+/// creating partition that already exists does nothing, so checking daily is plenty
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Creates monthly partitions of messages ahead of time, starting right away. Never returns.
+///
+/// Messages sent in month without partition are rejected by database, so every failure is logged
+/// and counted in `collector_partition_failures_total` while there are still months to spare.
+pub async fn run(pool: DbPool) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let months_ahead = get_config_async!().await.message_partitions_ahead_months;
+        let pool = pool.clone();
+
+        // diesel is blocking, so DDL stays away from async workers
+        let created = tokio::task::spawn_blocking(move || create_blocking(&pool, months_ahead))
+            .await
+            .unwrap_or_else(|err| {
+                error!("creating messages partitions panicked: {err:?}");
+                false
+            });
+
+        if !created {
+            metrics::PARTITION_FAILURES.inc();
+        }
+    }
+}
+
+fn create_blocking(pool: &DbPool, months_ahead: u32) -> bool {
+    let Ok(db_conn) = pool.get() else {
+        error!("couldn't get connection to database from pool to create messages partitions");
+        return false;
+    };
+
+    match messages::create_partitions(&db_conn, Utc::now(), months_ahead) {
+        Ok(()) => true,
+        Err(err) => {
+            error!("couldn't create messages partitions: {err:?}");
+            false
+        }
+    }
+}
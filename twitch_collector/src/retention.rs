@@ -0,0 +1,206 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use common::{
+    config::RetentionConfig,
+    models::{channel::Channel, message::Message},
+    services::{
+        resubs,
+        retention::{self, PruneReport, RetentionError},
+    },
+};
+use diesel::PgConnection;
+use error_stack::{IntoReport, Report, Result, ResultExt};
+
+use crate::{metrics, twitch_watcher::DbPool};
+
+/// Outcome of pruning every channel with retention
+#[derive(Default)]
+pub struct PruneRun {
+    pub reports: Vec<PruneReport>,
+    pub orphaned_resubs: usize,
+    /// channels that couldn't be pruned, the others were pruned anyway
+    pub failed_channels: Vec<String>,
+    /// other instance was pruning, so nothing was done
+    pub skipped: bool,
+}
+
+/// Prunes expired messages on schedule from `retention` config, returns right away without it.
+/// Never returns otherwise.
+pub async fn run(pool: DbPool) {
+    let Some(interval_secs) = get_config_async!()
+        .await
+        .retention
+        .as_ref()
+        .map(|retention| retention.interval_secs)
+    else {
+        return;
+    };
+
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+
+    loop {
+        interval.tick().await;
+
+        match prune(pool.clone()).await {
+            Ok(run) => log_run(&run),
+            Err(err) => error!("couldn't prune expired messages: {err:?}"),
+        }
+    }
+}
+
+/// Prunes every channel with retention once, for `--prune` and the schedule
+pub async fn prune(pool: DbPool) -> Result<PruneRun, RetentionError> {
+    let (batch_size, archive_dir) = {
+        let config = get_config_async!().await;
+        let default = RetentionConfig::default();
+        let retention = config.retention.as_ref().unwrap_or(&default);
+
+        (retention.batch_size, retention.archive_dir.clone())
+    };
+
+    // diesel is blocking, so pruning stays away from async workers
+    tokio::task::spawn_blocking(move || prune_blocking(&pool, batch_size, archive_dir))
+        .await
+        .into_report()
+        .change_context(RetentionError::Database)
+        .attach_printable("prune task panicked")?
+}
+
+fn log_run(run: &PruneRun) {
+    if run.skipped {
+        info!("other instance is pruning expired messages, skipping");
+        return;
+    }
+
+    for report in &run.reports {
+        if report.messages > 0 {
+            info!(
+                "pruned {} message(s) and {} resub(s) of channel {}",
+                report.messages, report.resubs, report.channel_name
+            );
+        }
+    }
+
+    if run.orphaned_resubs > 0 {
+        info!("pruned {} orphaned resub(s)", run.orphaned_resubs);
+    }
+}
+
+fn prune_blocking(
+    pool: &DbPool,
+    batch_size: i64,
+    archive_dir: Option<String>,
+) -> Result<PruneRun, RetentionError> {
+    let db_conn = pool
+        .get()
+        .into_report()
+        .change_context(RetentionError::Database)
+        .attach_printable("couldn't get connection to database from pool")?;
+
+    if !retention::try_lock(&db_conn).change_context(RetentionError::Database)? {
+        return Ok(PruneRun {
+            skipped: true,
+            ..Default::default()
+        });
+    }
+    let _lock = PruneLock(&db_conn);
+
+    prune_channels(&db_conn, batch_size, archive_dir.as_deref())
+}
+
+/// Releases prune lock when dropped. Lock is held by the connection, which goes back to pool
+/// even when pruning panics, and would keep every later prune skipped otherwise.
+struct PruneLock<'a>(&'a PgConnection);
+
+impl Drop for PruneLock<'_> {
+    fn drop(&mut self) {
+        if let Err(err) = retention::unlock(self.0) {
+            error!("{err:?}");
+        }
+    }
+}
+
+fn prune_channels(
+    db_conn: &PgConnection,
+    batch_size: i64,
+    archive_dir: Option<&str>,
+) -> Result<PruneRun, RetentionError> {
+    let channels =
+        retention::get_channels_with_retention(db_conn).change_context(RetentionError::Database)?;
+    let now = Utc::now();
+    let mut run = PruneRun::default();
+
+    for channel in channels {
+        let result =
+            retention::prune_channel(db_conn, &channel, now, batch_size, |channel, messages| {
+                match archive_dir {
+                    Some(archive_dir) => archive(archive_dir, channel, messages, now),
+                    None => Ok(()),
+                }
+            });
+
+        match result {
+            Ok(report) => {
+                metrics::MESSAGES_PRUNED
+                    .with_label_values(&[&report.channel_name])
+                    .inc_by(report.messages as u64);
+                run.reports.push(report);
+            }
+            Err(err) => {
+                // one channel shouldn't stop pruning of the others
+                error!("couldn't prune channel {}: {err:?}", channel.channel_name);
+                run.failed_channels.push(channel.channel_name);
+            }
+        }
+    }
+
+    run.orphaned_resubs =
+        resubs::delete_orphaned(db_conn).change_context(RetentionError::Database)?;
+
+    Ok(run)
+}
+
+/// Appends `messages` as json lines to `<archive_dir>/<channel>/<date of pruning>.jsonl`
+fn archive(
+    archive_dir: &str,
+    channel: &Channel,
+    messages: &[Message],
+    now: DateTime<Utc>,
+) -> Result<(), RetentionError> {
+    let dir = PathBuf::from(archive_dir).join(&channel.channel_name);
+    let path = dir.join(format!("{}.jsonl", now.format("%Y-%m-%d")));
+
+    let mut lines = String::new();
+    for message in messages {
+        let line = serde_json::to_string(message)
+            .into_report()
+            .change_context(RetentionError::Archive)?;
+        lines.push_str(&line);
+        lines.push('\n');
+    }
+
+    append_synced(&path, lines.as_bytes()).map_err(|err| {
+        Report::new(err)
+            .change_context(RetentionError::Archive)
+            .attach_printable(format!("couldn't write {}", path.display()))
+    })
+}
+
+/// Appends `lines` and syncs them to disk, messages are deleted right after
+fn append_synced(path: &Path, lines: &[u8]) -> io::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(lines)?;
+    file.sync_all()?;
+
+    // new file is stored in directory, which has to be synced on its own
+    File::open(dir)?.sync_all()
+}
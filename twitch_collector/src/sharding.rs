@@ -0,0 +1,344 @@
+use std::{collections::HashMap, fmt::Display, sync::Arc, time::Duration};
+
+use chrono::Utc;
+use common::{
+    config::ChannelConfig,
+    models::channel::Channel,
+    services::{channel_leases, channels, collector_instances},
+};
+use diesel::PgConnection;
+use error_stack::{IntoReport, Result, ResultExt};
+use lazy_static::lazy_static;
+use twitch_api2::HelixClient;
+use twitch_irc::{login::LoginCredentials, SecureTCPTransport, TwitchIRCClient};
+
+use crate::{
+    coverage, metrics,
+    token_manager::TokenManager,
+    twitch_watcher::{self, DbPool, JoinedChannels},
+};
+
+lazy_static! {
+    static ref DEFAULT_INSTANCE_ID: String = format!(
+        "{}-{}",
+        std::env::var("HOSTNAME").unwrap_or_else(|_| "collector".to_owned()),
+        std::process::id()
+    );
+}
+
+#[derive(Debug)]
+pub enum ShardingError {
+    DbPool,
+    Database,
+    Resolve,
+}
+
+impl Display for ShardingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShardingError::DbPool => write!(f, "Couldn't get connection to database from pool"),
+            ShardingError::Database => write!(f, "Database error"),
+            ShardingError::Resolve => write!(f, "Couldn't resolve channel"),
+        }
+    }
+}
+
+impl std::error::Error for ShardingError {}
+
+/// Keeps leases of this instance alive, takes its fair share of free channels and gives up
+/// channels above it, then joins/parts channels to match held leases. Never returns.
+pub async fn run<L: LoginCredentials>(
+    pool: DbPool,
+    helix_client: HelixClient<'static, reqwest::Client>,
+    token_manager: Arc<TokenManager>,
+    client: TwitchIRCClient<SecureTCPTransport, L>,
+    joined_channels: JoinedChannels,
+) {
+    let config = get_config_async!().await;
+    let Some(sharding_config) = &config.sharding else {
+        return;
+    };
+
+    let instance_id = instance_id(sharding_config.instance_id.as_ref());
+    let lease_duration = chrono::Duration::seconds(sharding_config.lease_duration_secs as i64);
+    let renew_interval = Duration::from_secs(sharding_config.lease_renew_interval_secs);
+    drop(config);
+
+    info!("Sharding channels as instance {instance_id}");
+
+    // channels from config by the name they have there
+    let mut candidates: HashMap<String, Channel> = HashMap::new();
+    let mut interval = tokio::time::interval(renew_interval);
+
+    loop {
+        interval.tick().await;
+
+        // list is read on every tick so config reload can change it
+        let config_channels = get_config_async!().await.channels.clone();
+        candidates.retain(|name, _| config_channels.iter().any(|channel| &channel.name == name));
+
+        // channels that couldn't be resolved are tried again on every tick, changed policies
+        // of resolved ones are stored
+        for channel in &config_channels {
+            let result = match candidates.remove(&channel.name) {
+                Some(db_channel) if db_channel.policy() == channel.policy => Ok(db_channel),
+                Some(db_channel) => update_policy(&pool, db_channel, channel),
+                None => {
+                    twitch_watcher::resolve_channel(&pool, &helix_client, &token_manager, channel)
+                        .await
+                        .change_context(ShardingError::Resolve)
+                }
+            };
+
+            match result {
+                Ok(db_channel) => {
+                    candidates.insert(channel.name.clone(), db_channel);
+                }
+                Err(err) => {
+                    metrics::JOIN_FAILURES
+                        .with_label_values(&[&channel.name])
+                        .inc();
+                    error!("couldn't resolve channel {}: {err:?}", channel.name);
+                }
+            }
+        }
+
+        // leases of paused channels are released, so they get parted
+        let candidate_ids: Vec<i32> = candidates
+            .values()
+            .filter(|channel| !channel.paused)
+            .map(|channel| channel.id)
+            .collect();
+
+        let held = pool
+            .get()
+            .into_report()
+            .change_context(ShardingError::DbPool)
+            .and_then(|db_conn| rebalance(&db_conn, &instance_id, &candidate_ids, lease_duration));
+
+        match held {
+            Ok(held) => sync_joins(&pool, &client, &joined_channels, &held).await,
+            Err(err) => error!("couldn't rebalance channels: {err:?}"),
+        }
+    }
+}
+
+/// Gives up every lease of this instance so others don't have to wait for them to expire
+pub async fn release_all(pool: &DbPool) {
+    let config = get_config_async!().await;
+    let Some(sharding_config) = &config.sharding else {
+        return;
+    };
+    let instance_id = instance_id(sharding_config.instance_id.as_ref());
+    drop(config);
+
+    let Ok(db_conn) = pool.get() else {
+        error!("couldn't get connection to database from pool to release channel leases");
+        return;
+    };
+
+    if let Err(err) = channel_leases::release_all(&db_conn, &instance_id)
+        .and_then(|_| collector_instances::remove(&db_conn, &instance_id))
+    {
+        error!("couldn't release channel leases: {err:?}");
+    }
+}
+
+fn update_policy(
+    pool: &DbPool,
+    db_channel: Channel,
+    channel: &ChannelConfig,
+) -> Result<Channel, ShardingError> {
+    let db_conn = pool
+        .get()
+        .into_report()
+        .change_context(ShardingError::DbPool)?;
+
+    channels::set_policy(&db_conn, db_channel, &channel.policy)
+        .change_context(ShardingError::Database)
+}
+
+fn instance_id(configured: Option<&String>) -> String {
+    configured.unwrap_or(&DEFAULT_INSTANCE_ID).clone()
+}
+
+/// Renews leases and moves held channels towards fair share, returns ids of held channels
+fn rebalance(
+    db_conn: &PgConnection,
+    instance_id: &str,
+    candidate_ids: &[i32],
+    lease_duration: chrono::Duration,
+) -> Result<Vec<i32>, ShardingError> {
+    let now = Utc::now();
+    let expires_at = now + lease_duration;
+
+    collector_instances::heartbeat(db_conn, instance_id, now)
+        .change_context(ShardingError::Database)?;
+
+    // count includes this instance as it just sent heartbeat
+    let alive_instances = collector_instances::count_alive(db_conn, now - lease_duration)
+        .change_context(ShardingError::Database)?;
+    let fair_share = fair_share(candidate_ids.len(), alive_instances as usize);
+
+    let held = channel_leases::renew(db_conn, instance_id, expires_at)
+        .change_context(ShardingError::Database)?;
+
+    let (mut held, released) = split_held(held, candidate_ids, fair_share);
+
+    if !released.is_empty() {
+        info!("releasing {} channel(s) to other instances", released.len());
+
+        channel_leases::release(db_conn, instance_id, &released)
+            .change_context(ShardingError::Database)?;
+    }
+
+    if held.len() < fair_share {
+        let taken = channel_leases::get_taken_channel_ids(db_conn, now)
+            .change_context(ShardingError::Database)?;
+
+        for &channel_id in candidate_ids {
+            if held.len() >= fair_share {
+                break;
+            }
+
+            if taken.contains(&channel_id) {
+                continue;
+            }
+
+            if channel_leases::claim(db_conn, channel_id, instance_id, expires_at, now)
+                .change_context(ShardingError::Database)?
+            {
+                held.push(channel_id);
+            }
+        }
+    }
+
+    Ok(held)
+}
+
+/// Channels one instance should hold, rounded up so every channel has an instance
+fn fair_share(channels: usize, alive_instances: usize) -> usize {
+    channels.div_ceil(alive_instances.max(1))
+}
+
+/// Splits held channels into ones to keep and ones to release, channels removed from config and
+/// channels above fair share go to other instances
+fn split_held(
+    mut held: Vec<i32>,
+    candidate_ids: &[i32],
+    fair_share: usize,
+) -> (Vec<i32>, Vec<i32>) {
+    let mut released: Vec<i32> = held
+        .iter()
+        .copied()
+        .filter(|channel_id| !candidate_ids.contains(channel_id))
+        .collect();
+    held.retain(|channel_id| candidate_ids.contains(channel_id));
+
+    if held.len() > fair_share {
+        released.extend(held.split_off(fair_share));
+    }
+
+    (held, released)
+}
+
+/// Joins channels whose lease this instance got and parts the ones it lost
+async fn sync_joins<L: LoginCredentials>(
+    pool: &DbPool,
+    client: &TwitchIRCClient<SecureTCPTransport, L>,
+    joined_channels: &JoinedChannels,
+    held: &[i32],
+) {
+    let Ok(db_conn) = pool.get() else {
+        error!("couldn't get connection to database from pool to join leased channels");
+        return;
+    };
+
+    let mut joined_channels = joined_channels.write().await;
+
+    for channel in joined_channels.iter() {
+        if !held.contains(&channel.id) {
+            info!(
+                "Parting channel {}, it's recorded by other instance",
+                channel.channel_name
+            );
+
+            client.part(channel.channel_name.clone());
+            coverage::stop_recording(&db_conn, channel.id);
+        }
+    }
+    joined_channels.retain(|channel| held.contains(&channel.id));
+
+    for &channel_id in held {
+        if joined_channels
+            .iter()
+            .any(|channel| channel.id == channel_id)
+        {
+            continue;
+        }
+
+        // name in db is the current one, it might have been renamed since it was resolved
+        let channel = match channels::get_channel_by_id(&db_conn, channel_id) {
+            Ok(Some(channel)) => channel,
+            Ok(None) => continue,
+            Err(err) => {
+                error!("{err:?}");
+                continue;
+            }
+        };
+
+        info!("Joining channel {}", channel.channel_name);
+
+        match client.join(channel.channel_name.clone()) {
+            Ok(()) => joined_channels.push(channel),
+            Err(err) => {
+                metrics::JOIN_FAILURES
+                    .with_label_values(&[&channel.channel_name])
+                    .inc();
+                error!("couldn't join channel {}: {err}", channel.channel_name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fair_share_rounds_up() {
+        assert_eq!(fair_share(10, 3), 4);
+        assert_eq!(fair_share(9, 3), 3);
+        assert_eq!(fair_share(1, 4), 1);
+        assert_eq!(fair_share(0, 2), 0);
+    }
+
+    #[test]
+    fn fair_share_counts_at_least_one_instance() {
+        assert_eq!(fair_share(5, 0), 5);
+    }
+
+    #[test]
+    fn split_held_releases_channels_removed_from_config() {
+        let (held, released) = split_held(vec![1, 2, 3], &[1, 3], 5);
+
+        assert_eq!(held, vec![1, 3]);
+        assert_eq!(released, vec![2]);
+    }
+
+    #[test]
+    fn split_held_releases_channels_above_fair_share() {
+        let (held, released) = split_held(vec![1, 2, 3, 4], &[1, 2, 3, 4, 5], 2);
+
+        assert_eq!(held, vec![1, 2]);
+        assert_eq!(released, vec![3, 4]);
+    }
+
+    #[test]
+    fn split_held_keeps_everything_under_fair_share() {
+        let (held, released) = split_held(vec![1, 2], &[1, 2, 3], 2);
+
+        assert_eq!(held, vec![1, 2]);
+        assert!(released.is_empty());
+    }
+}
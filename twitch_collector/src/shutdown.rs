@@ -0,0 +1,22 @@
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::watch,
+};
+
+/// Returns receiver that changes to `true` once SIGTERM or SIGINT is received
+pub fn listen() -> watch::Receiver<bool> {
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+    tokio::spawn(async move {
+        let mut sigterm = signal(SignalKind::terminate()).expect("couldn't listen for SIGTERM");
+
+        tokio::select! {
+            _ = sigterm.recv() => info!("received SIGTERM"),
+            _ = tokio::signal::ctrl_c() => info!("received SIGINT"),
+        }
+
+        let _ = shutdown_tx.send(true);
+    });
+
+    shutdown_rx
+}
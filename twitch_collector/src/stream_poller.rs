@@ -0,0 +1,327 @@
+use std::{fmt::Display, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use common::{
+    models::{channel::Channel, stream_session::NewStreamSession},
+    services::{messages, stream_sessions},
+};
+use diesel::PgConnection;
+use error_stack::{IntoReport, Result, ResultExt};
+use twitch_api2::{
+    helix::streams::{GetStreamsRequest, Stream},
+    types::UserId,
+    HelixClient,
+};
+
+use crate::{
+    config_reload::ConfigInterval,
+    token_manager::TokenManager,
+    twitch_watcher::{DbPool, JoinedChannels},
+};
+
+/// helix allows at most 100 user ids per get streams request
+const MAX_CHANNELS_PER_REQUEST: usize = 100;
+
+#[derive(Debug)]
+pub enum PollError {
+    Api,
+    DbPool,
+    Database,
+}
+
+impl Display for PollError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PollError::Api => write!(f, "Couldn't get streams from twitch api"),
+            PollError::DbPool => write!(f, "Couldn't get connection to database from pool"),
+            PollError::Database => write!(f, "Database error"),
+        }
+    }
+}
+
+impl std::error::Error for PollError {}
+
+/// Periodically checks which of joined `channels` are live and keeps `stream_sessions` up to date.
+/// Never returns, errors are only logged.
+pub async fn run(
+    pool: DbPool,
+    helix_client: HelixClient<'static, reqwest::Client>,
+    token_manager: Arc<TokenManager>,
+    channels: JoinedChannels,
+) {
+    let mut interval = ConfigInterval::new(|config| config.stream_poll_interval_secs).await;
+
+    loop {
+        interval.tick().await;
+
+        // channels joined later are picked up on next tick
+        let channels = channels.read().await.clone();
+
+        if let Err(err) = poll(&pool, &helix_client, &token_manager, &channels).await {
+            error!("couldn't poll streams: {err:?}");
+        }
+    }
+}
+
+async fn poll(
+    pool: &DbPool,
+    helix_client: &HelixClient<'static, reqwest::Client>,
+    token_manager: &TokenManager,
+    channels: &[Channel],
+) -> Result<(), PollError> {
+    for channels in channels.chunks(MAX_CHANNELS_PER_REQUEST) {
+        let streams: Vec<Stream> = token_manager
+            .call(|token| {
+                let request = GetStreamsRequest::builder()
+                    .user_id(
+                        channels
+                            .iter()
+                            .map(|channel| UserId::new(channel.twitch_channel_id.clone()))
+                            .collect::<Vec<_>>(),
+                    )
+                    .first(MAX_CHANNELS_PER_REQUEST)
+                    .build();
+
+                async move { helix_client.req_get(request, &token).await }
+            })
+            .await
+            .change_context(PollError::Api)?
+            .data;
+
+        let db_conn = pool.get().into_report().change_context(PollError::DbPool)?;
+
+        for channel in channels {
+            let stream = streams
+                .iter()
+                .find(|stream| stream.user_id.as_str() == channel.twitch_channel_id);
+
+            if let Err(err) = update_session(&db_conn, channel, stream) {
+                error!(
+                    "couldn't update stream session of channel {}: {err:?}",
+                    channel.channel_name
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// What polled `stream` means for open session of channel
+#[derive(Debug, PartialEq)]
+enum Transition {
+    /// channel is still offline
+    StayOffline,
+    /// channel went offline, session ends at `ended_at`
+    End {
+        session_id: i32,
+        ended_at: DateTime<Utc>,
+    },
+    /// same stream is still live, title or category might have changed
+    Continue,
+    /// new stream started at `started_at`, `previous` is id of open session and when it ends
+    Start {
+        started_at: DateTime<Utc>,
+        previous: Option<(i32, DateTime<Utc>)>,
+    },
+}
+
+/// `open_session` is id and twitch stream id of open session, `stream` is id and start of live
+/// stream
+fn transition(
+    open_session: Option<(i32, &str)>,
+    stream: Option<(&str, DateTime<Utc>)>,
+    now: DateTime<Utc>,
+) -> Transition {
+    match (stream, open_session) {
+        (None, None) => Transition::StayOffline,
+        (None, Some((session_id, _))) => Transition::End {
+            session_id,
+            ended_at: now,
+        },
+        (Some((stream_id, _)), Some((_, session_stream_id))) if stream_id == session_stream_id => {
+            Transition::Continue
+        }
+        // stream id changed without us seeing channel offline, so previous stream ended at the
+        // latest when new one started
+        (Some((_, started_at)), open_session) => Transition::Start {
+            started_at,
+            previous: open_session.map(|(session_id, _)| (session_id, now.min(started_at))),
+        },
+    }
+}
+
+fn update_session(
+    db_conn: &PgConnection,
+    channel: &Channel,
+    stream: Option<&Stream>,
+) -> Result<(), PollError> {
+    let now = Utc::now();
+
+    let open_session = stream_sessions::get_open_session(db_conn, channel.id)
+        .change_context(PollError::Database)?;
+
+    let started_at = stream.map(|stream| {
+        DateTime::parse_from_rfc3339(stream.started_at.as_str())
+            .map(|started_at| started_at.with_timezone(&Utc))
+            .unwrap_or(now)
+    });
+
+    let transition = transition(
+        open_session
+            .as_ref()
+            .map(|session| (session.id, session.twitch_stream_id.as_str())),
+        stream
+            .zip(started_at)
+            .map(|(stream, started_at)| (stream.id.as_str(), started_at)),
+        now,
+    );
+
+    match (transition, stream, open_session) {
+        (
+            Transition::End {
+                session_id,
+                ended_at,
+            },
+            _,
+            _,
+        ) => {
+            info!("channel {} went offline", channel.channel_name);
+
+            stream_sessions::end_session(db_conn, session_id, ended_at)
+                .change_context(PollError::Database)?;
+        }
+        (Transition::Continue, Some(stream), Some(open_session)) => {
+            stream_sessions::check_and_fix_info(
+                db_conn,
+                open_session,
+                &stream.title,
+                stream.game_id.as_str(),
+                &stream.game_name,
+                now,
+            )
+            .change_context(PollError::Database)?;
+        }
+        (
+            Transition::Start {
+                started_at,
+                previous,
+            },
+            Some(stream),
+            _,
+        ) => {
+            if let Some((session_id, ended_at)) = previous {
+                stream_sessions::end_session(db_conn, session_id, ended_at)
+                    .change_context(PollError::Database)?;
+            }
+
+            info!("channel {} went live", channel.channel_name);
+
+            let stream_session = stream_sessions::create(
+                db_conn,
+                NewStreamSession {
+                    channel_id: channel.id,
+                    twitch_stream_id: stream.id.as_str().to_owned(),
+                    title: stream.title.clone(),
+                    category_id: stream.game_id.as_str().to_owned(),
+                    category_name: stream.game_name.clone(),
+                    started_at,
+                },
+            )
+            .change_context(PollError::Database)?;
+
+            // messages sent after new stream started were linked to previous session if it was
+            // still open then
+            messages::link_to_stream_session(
+                db_conn,
+                channel.id,
+                stream_session.id,
+                started_at,
+                previous.map(|(session_id, _)| session_id),
+            )
+            .change_context(PollError::Database)?;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2022-11-30T20:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn offline_channel_stays_offline() {
+        assert_eq!(transition(None, None, now()), Transition::StayOffline);
+    }
+
+    #[test]
+    fn session_ends_when_channel_goes_offline() {
+        assert_eq!(
+            transition(Some((1, "100")), None, now()),
+            Transition::End {
+                session_id: 1,
+                ended_at: now()
+            }
+        );
+    }
+
+    #[test]
+    fn same_stream_continues_session() {
+        let started_at = now() - Duration::hours(2);
+
+        assert_eq!(
+            transition(Some((1, "100")), Some(("100", started_at)), now()),
+            Transition::Continue
+        );
+    }
+
+    #[test]
+    fn stream_starts_session_when_channel_goes_live() {
+        let started_at = now() - Duration::minutes(1);
+
+        assert_eq!(
+            transition(None, Some(("100", started_at)), now()),
+            Transition::Start {
+                started_at,
+                previous: None
+            }
+        );
+    }
+
+    #[test]
+    fn changed_stream_ends_previous_session_when_new_one_started() {
+        let started_at = now() - Duration::minutes(1);
+
+        assert_eq!(
+            transition(Some((1, "100")), Some(("101", started_at)), now()),
+            Transition::Start {
+                started_at,
+                previous: Some((1, started_at))
+            }
+        );
+    }
+
+    #[test]
+    fn changed_stream_starting_in_future_ends_previous_session_now() {
+        // clocks of twitch and collector can differ a bit
+        let started_at = now() + Duration::seconds(5);
+
+        assert_eq!(
+            transition(Some((1, "100")), Some(("101", started_at)), now()),
+            Transition::Start {
+                started_at,
+                previous: Some((1, now()))
+            }
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    cmp::min,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::{sync::watch, task::JoinHandle};
+use twitch_api2::HelixClient;
+
+use crate::{
+    token_manager::TokenManager,
+    twitch_watcher::{self, DbPool, RunError},
+};
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+/// irc client that ran at least this long is considered healthy, so backoff starts over
+const HEALTHY_RUN: Duration = Duration::from_secs(10 * 60);
+
+/// Exponential backoff between retries, doubles up to `max`
+pub struct Backoff {
+    current: Duration,
+    initial: Duration,
+    max: Duration,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            current: initial,
+            initial,
+            max,
+        }
+    }
+
+    /// Returns delay before next retry and increases it for the one after
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = min(self.current * 2, self.max);
+        delay
+    }
+
+    pub fn reset(&mut self) {
+        self.current = self.initial;
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(INITIAL_BACKOFF, MAX_BACKOFF)
+    }
+}
+
+/// Tasks that are aborted when the group is dropped, so nothing outlives the irc client they
+/// were spawned for
+#[derive(Default)]
+pub struct TaskGroup(Vec<JoinHandle<()>>);
+
+impl TaskGroup {
+    pub fn spawn<F>(&mut self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.0.push(tokio::spawn(future));
+    }
+}
+
+impl Drop for TaskGroup {
+    fn drop(&mut self) {
+        for handle in &self.0 {
+            handle.abort();
+        }
+    }
+}
+
+/// Keeps collector running: restarts irc client whenever it stops and retries getting app
+/// access token until it succeeds. Returns only after graceful shutdown.
+pub async fn run(
+    pool: DbPool,
+    helix_client: HelixClient<'static, reqwest::Client>,
+    mut shutdown: watch::Receiver<bool>,
+) -> error_stack::Result<(), RunError> {
+    let mut backoff = Backoff::default();
+
+    let token_manager = loop {
+        match TokenManager::new(reqwest::Client::new()).await {
+            Ok(token_manager) => break Arc::new(token_manager),
+            Err(err) => {
+                error!("couldn't get app access token: {err:?}");
+
+                if !wait_or_shutdown(backoff.next_delay(), &mut shutdown).await {
+                    return Ok(());
+                }
+            }
+        }
+    };
+    backoff.reset();
+
+    let mut tasks = TaskGroup::default();
+    tasks.spawn(token_manager.clone().run());
+
+    loop {
+        let started = Instant::now();
+
+        let result = twitch_watcher::run(
+            pool.clone(),
+            helix_client.clone(),
+            token_manager.clone(),
+            shutdown.clone(),
+        )
+        .await;
+
+        if *shutdown.borrow() {
+            return result;
+        }
+
+        match result {
+            Ok(()) => return Ok(()),
+            Err(err) => {
+                println!("Irc client stopped, restarting. Check logs for details");
+                error!("irc client stopped, restarting: {err:?}");
+            }
+        }
+
+        if started.elapsed() > HEALTHY_RUN {
+            backoff.reset();
+        }
+
+        if !wait_or_shutdown(backoff.next_delay(), &mut shutdown).await {
+            return Ok(());
+        }
+    }
+}
+
+/// Sleeps for `delay`, returns false if shutdown was requested in the meantime
+pub async fn wait_or_shutdown(delay: Duration, shutdown: &mut watch::Receiver<bool>) -> bool {
+    if *shutdown.borrow() {
+        return false;
+    }
+
+    tokio::select! {
+        _ = tokio::time::sleep(delay) => true,
+        _ = shutdown.changed() => false,
+    }
+}
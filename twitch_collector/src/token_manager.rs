@@ -0,0 +1,321 @@
+use std::{fmt::Display, future::Future, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use error_stack::{IntoReport, Result, ResultExt};
+use reqwest::StatusCode;
+use tokio::sync::RwLock;
+use twitch_api2::{
+    helix::{ClientRequestError, HelixRequestGetError},
+    twitch_oauth2::{AppAccessToken, ClientId, ClientSecret, Scope, TwitchToken},
+};
+
+/// token is refreshed this long before it expires
+const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+/// wait between attempts when refreshing in background fails
+const REFRESH_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+pub type HelixRequestError = ClientRequestError<reqwest::Error>;
+
+#[derive(Debug)]
+pub enum TokenError {
+    GetToken,
+    Api,
+}
+
+impl Display for TokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenError::GetToken => write!(f, "Couldn't get token from twitch"),
+            TokenError::Api => write!(f, "Couldn't get info from twitch api"),
+        }
+    }
+}
+
+impl std::error::Error for TokenError {}
+
+/// Where [`TokenManager`] gets new tokens from
+#[async_trait]
+pub trait TokenSource: Send + Sync {
+    async fn get_token(&self) -> Result<AppAccessToken, TokenError>;
+}
+
+/// Gets app access token from twitch with client id and secret from config
+pub struct TwitchTokenSource {
+    // plain reqwest client, HelixClient as oauth client makes futures not Send
+    http_client: reqwest::Client,
+}
+
+#[async_trait]
+impl TokenSource for TwitchTokenSource {
+    async fn get_token(&self) -> Result<AppAccessToken, TokenError> {
+        get_app_access_token(&self.http_client).await
+    }
+}
+
+/// Keeps app access token valid for every helix user in the collector.
+///
+/// Share it as `Arc<TokenManager>` and make helix calls through [`TokenManager::call`].
+pub struct TokenManager {
+    source: Box<dyn TokenSource>,
+    token: RwLock<AppAccessToken>,
+}
+
+impl TokenManager {
+    pub async fn new(http_client: reqwest::Client) -> Result<Self, TokenError> {
+        Self::with_source(TwitchTokenSource { http_client }).await
+    }
+
+    pub async fn with_source(source: impl TokenSource + 'static) -> Result<Self, TokenError> {
+        let token = source.get_token().await?;
+
+        Ok(Self {
+            source: Box::new(source),
+            token: RwLock::new(token),
+        })
+    }
+
+    /// Returns current token, refreshing it first if it's about to expire
+    pub async fn token(&self) -> Result<AppAccessToken, TokenError> {
+        let token = self.token.read().await.clone();
+
+        if token.expires_in() > REFRESH_MARGIN {
+            return Ok(token);
+        }
+
+        self.refresh(&token).await
+    }
+
+    /// Refreshes token unless it was already replaced by someone else since `used` was handed out
+    pub async fn refresh(&self, used: &AppAccessToken) -> Result<AppAccessToken, TokenError> {
+        let mut token = self.token.write().await;
+
+        if token.token().secret() != used.token().secret() {
+            return Ok(token.clone());
+        }
+
+        info!("refreshing app access token");
+        *token = self.source.get_token().await?;
+
+        Ok(token.clone())
+    }
+
+    /// Calls helix with current token, on 401 refreshes the token and tries once more
+    pub async fn call<T, F, Fut>(&self, request: F) -> Result<T, TokenError>
+    where
+        F: Fn(AppAccessToken) -> Fut,
+        Fut: Future<Output = std::result::Result<T, HelixRequestError>>,
+    {
+        let token = self.token().await?;
+
+        match request(token.clone()).await {
+            Err(ClientRequestError::HelixRequestGetError(HelixRequestGetError::Error {
+                status: StatusCode::UNAUTHORIZED,
+                ..
+            })) => {
+                warn!("helix returned 401, refreshing app access token");
+
+                let token = self.refresh(&token).await?;
+
+                request(token)
+                    .await
+                    .into_report()
+                    .change_context(TokenError::Api)
+            }
+            result => result.into_report().change_context(TokenError::Api),
+        }
+    }
+
+    /// Refreshes token ahead of its expiry. Never returns.
+    pub async fn run(self: Arc<Self>) {
+        loop {
+            let token = self.token.read().await.clone();
+            tokio::time::sleep(refresh_delay(&token)).await;
+
+            if let Err(err) = self.refresh(&token).await {
+                error!("couldn't refresh app access token: {err:?}");
+                tokio::time::sleep(REFRESH_RETRY_DELAY).await;
+            }
+        }
+    }
+}
+
+/// How long `token` can be used before it has to be refreshed
+fn refresh_delay(token: &AppAccessToken) -> Duration {
+    token.expires_in().saturating_sub(REFRESH_MARGIN)
+}
+
+async fn get_app_access_token(http_client: &reqwest::Client) -> Result<AppAccessToken, TokenError> {
+    // guard isn't held during the request so config reload doesn't have to wait for it
+    let (client_id, client_secret) = {
+        let config = get_config_async!().await;
+        (
+            config.twitchapi.clientid.clone(),
+            config.twitchapi.clientsecret.clone(),
+        )
+    };
+
+    AppAccessToken::get_app_access_token(
+        http_client,
+        ClientId::new(client_id),
+        ClientSecret::new(client_secret),
+        Scope::all(),
+    )
+    .await
+    .into_report()
+    .change_context(TokenError::GetToken)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use twitch_api2::twitch_oauth2::AccessToken;
+
+    use super::*;
+
+    /// Hands out tokens "token-1", "token-2", ... that expire after `expires_in`
+    struct CountingSource {
+        fetched: Arc<AtomicUsize>,
+        expires_in: Duration,
+    }
+
+    #[async_trait]
+    impl TokenSource for CountingSource {
+        async fn get_token(&self) -> Result<AppAccessToken, TokenError> {
+            let n = self.fetched.fetch_add(1, Ordering::SeqCst) + 1;
+
+            Ok(token(&format!("token-{n}"), self.expires_in))
+        }
+    }
+
+    fn token(secret: &str, expires_in: Duration) -> AppAccessToken {
+        AppAccessToken::from_existing_unchecked(
+            AccessToken::new(secret.to_owned()),
+            None,
+            ClientId::new("client".to_owned()),
+            ClientSecret::new("secret".to_owned()),
+            None,
+            Some(expires_in),
+        )
+    }
+
+    async fn manager(expires_in: Duration) -> (TokenManager, Arc<AtomicUsize>) {
+        let fetched = Arc::new(AtomicUsize::new(0));
+        let manager = TokenManager::with_source(CountingSource {
+            fetched: fetched.clone(),
+            expires_in,
+        })
+        .await
+        .unwrap();
+
+        (manager, fetched)
+    }
+
+    fn unauthorized() -> HelixRequestError {
+        ClientRequestError::HelixRequestGetError(HelixRequestGetError::Error {
+            error: "Unauthorized".to_owned(),
+            status: StatusCode::UNAUTHORIZED,
+            message: "Invalid OAuth token".to_owned(),
+            uri: Default::default(),
+        })
+    }
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    #[tokio::test]
+    async fn valid_token_is_reused() {
+        let (manager, fetched) = manager(HOUR).await;
+
+        let secret = manager
+            .call(|token| async move { Ok(token.token().secret().to_owned()) })
+            .await
+            .unwrap();
+
+        assert_eq!(secret, "token-1");
+        assert_eq!(fetched.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn token_about_to_expire_is_refreshed_before_call() {
+        let (manager, fetched) = manager(REFRESH_MARGIN - Duration::from_secs(1)).await;
+
+        let secret = manager
+            .call(|token| async move { Ok(token.token().secret().to_owned()) })
+            .await
+            .unwrap();
+
+        assert_eq!(secret, "token-2");
+        assert_eq!(fetched.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn unauthorized_call_is_retried_once_with_refreshed_token() {
+        let (manager, fetched) = manager(HOUR).await;
+        let calls = AtomicUsize::new(0);
+
+        let secret = manager
+            .call(|token| {
+                let first = calls.fetch_add(1, Ordering::SeqCst) == 0;
+
+                async move {
+                    if first {
+                        Err(unauthorized())
+                    } else {
+                        Ok(token.token().secret().to_owned())
+                    }
+                }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(secret, "token-2");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(fetched.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn second_unauthorized_call_fails_without_another_refresh() {
+        let (manager, fetched) = manager(HOUR).await;
+        let calls = AtomicUsize::new(0);
+
+        let result = manager
+            .call(|_| {
+                calls.fetch_add(1, Ordering::SeqCst);
+
+                async { Err::<(), _>(unauthorized()) }
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(fetched.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn refresh_of_replaced_token_returns_current_one() {
+        let (manager, fetched) = manager(HOUR).await;
+        let old = manager.token().await.unwrap();
+
+        manager.refresh(&old).await.unwrap();
+        let current = manager.refresh(&old).await.unwrap();
+
+        assert_eq!(current.token().secret(), "token-2");
+        assert_eq!(fetched.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn refresh_is_scheduled_margin_before_expiry() {
+        let delay = refresh_delay(&token("token", HOUR));
+
+        assert!(delay <= HOUR - REFRESH_MARGIN);
+        assert!(delay > HOUR - REFRESH_MARGIN - Duration::from_secs(5));
+    }
+
+    #[test]
+    fn token_expiring_within_margin_is_refreshed_right_away() {
+        assert_eq!(
+            refresh_delay(&token("token", REFRESH_MARGIN / 2)),
+            Duration::ZERO
+        );
+    }
+}
@@ -1,13 +1,25 @@
-use std::fmt::{Debug, Display};
+use std::{
+    fmt::{Debug, Display},
+    sync::Arc,
+    time::Duration,
+};
 
+use chrono::Utc;
 use common::{
+    config::{ChannelConfig, TokenStorageKind},
     models::{
+        channel::{Channel, EventKind},
         message::MsgType,
         resub::{NewResub, Tier},
+        room_state_change::NewRoomStateChange,
     },
     services::{
-        channels::{create_channel_if_not_exists, get_channel_by_twitch_id},
+        channels::{
+            create_channel_if_not_exists, get_channel_by_name, get_channel_by_old_name,
+            get_channel_by_twitch_id, set_policy,
+        },
         messages::create_message,
+        room_state_changes,
     },
 };
 use diesel::{
@@ -15,174 +27,491 @@ use diesel::{
     PgConnection,
 };
 use error_stack::{IntoReport, Report, ResultExt};
-use tokio::spawn;
-use twitch_api2::{
-    twitch_oauth2::{AppAccessToken, ClientId, ClientSecret, Scope},
-    HelixClient,
+use tokio::{
+    spawn,
+    sync::{mpsc, watch, RwLock},
+    time::timeout,
 };
+use twitch_api2::{helix::channels::ChannelInformation, HelixClient};
 use twitch_irc::{
-    login::StaticLoginCredentials,
-    message::{PrivmsgMessage, ServerMessage, UserNoticeEvent, UserNoticeMessage},
+    login::{LoginCredentials, RefreshingLoginCredentials},
+    message::{
+        FollowersOnlyMode, PrivmsgMessage, RoomStateMessage, ServerMessage, UserNoticeEvent,
+        UserNoticeMessage,
+    },
     ClientConfig, SecureTCPTransport, TwitchIRCClient,
 };
 
-type DbPool = Pool<ConnectionManager<PgConnection>>;
-type PooledConnection = diesel::r2d2::PooledConnection<ConnectionManager<PgConnection>>;
+use crate::{
+    channel_reconciler, config_channels, coverage,
+    irc_login::{DatabaseTokenStorage, FileTokenStorage},
+    logging, metrics, sharding, stream_poller,
+    supervisor::TaskGroup,
+    token_manager::TokenManager,
+};
+
+pub type DbPool = Pool<ConnectionManager<PgConnection>>;
+/// channels irc client is currently joined to, shared with pollers
+pub type JoinedChannels = Arc<RwLock<Vec<Channel>>>;
+pub type PooledConnection = diesel::r2d2::PooledConnection<ConnectionManager<PgConnection>>;
 
 #[derive(Debug)]
 pub enum RunError {
     HandleError,
-    GetTokenError,
     ApiError,
     ChannelNotExists(String),
+    InvalidChannelName(String),
     DbPoolError,
     DatabaseError,
+    ShutdownTimeout,
 }
 
 impl Display for RunError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             RunError::HandleError => write!(f, "Unexpected Error!"),
-            RunError::GetTokenError => write!(f, "Couldn't get token from twitch"),
             RunError::ApiError => write!(f, "Couldn't get info from twitch api"),
             RunError::ChannelNotExists(name) => {
                 write!(f, "Couldn't find channel with name \"{name}\"")
             }
+            RunError::InvalidChannelName(name) => {
+                write!(f, "\"{name}\" isn't valid channel name")
+            }
             RunError::DbPoolError => write!(f, "Couldn't get connection to database from pool"),
             RunError::DatabaseError => write!(f, "Database error"),
+            RunError::ShutdownTimeout => {
+                write!(f, "Some messages weren't saved before shutdown timeout")
+            }
         }
     }
 }
 
 impl std::error::Error for RunError {}
 
-// will return only on error
+// will return Ok only after graceful shutdown triggered by `shutdown`
 pub async fn run(
     pool: DbPool,
     helix_client: HelixClient<'static, reqwest::Client>,
+    token_manager: Arc<TokenManager>,
+    shutdown: watch::Receiver<bool>,
 ) -> error_stack::Result<(), RunError> {
-    let client_config = ClientConfig::default();
+    let config = get_config_async!().await;
+
+    let Some(irc_config) = &config.irc else {
+        drop(config);
+        info!("Logging into irc anonymously");
+
+        return collect(
+            pool,
+            helix_client,
+            token_manager,
+            ClientConfig::default(),
+            shutdown,
+        )
+        .await;
+    };
 
+    info!("Logging into irc as {}", irc_config.login);
+
+    let login = irc_config.login.clone();
+    let token_storage = irc_config.token_storage;
+    let token_file = irc_config.token_file.clone();
+    let client_id = config.twitchapi.clientid.clone();
+    let client_secret = config.twitchapi.clientsecret.clone();
+    drop(config);
+
+    match token_storage {
+        TokenStorageKind::Database => {
+            let token_storage = DatabaseTokenStorage::new(pool.clone(), login.clone(), token_file);
+            let login_credentials = RefreshingLoginCredentials::init_with_username(
+                Some(login),
+                client_id,
+                client_secret,
+                token_storage,
+            );
+
+            collect(
+                pool,
+                helix_client,
+                token_manager,
+                ClientConfig::new_simple(login_credentials),
+                shutdown,
+            )
+            .await
+        }
+        TokenStorageKind::File => {
+            let token_storage = FileTokenStorage::new(token_file);
+            let login_credentials = RefreshingLoginCredentials::init_with_username(
+                Some(login),
+                client_id,
+                client_secret,
+                token_storage,
+            );
+
+            collect(
+                pool,
+                helix_client,
+                token_manager,
+                ClientConfig::new_simple(login_credentials),
+                shutdown,
+            )
+            .await
+        }
+    }
+}
+
+async fn collect<L: LoginCredentials>(
+    pool: DbPool,
+    helix_client: HelixClient<'static, reqwest::Client>,
+    token_manager: Arc<TokenManager>,
+    client_config: ClientConfig<L>,
+    mut shutdown: watch::Receiver<bool>,
+) -> error_stack::Result<(), RunError> {
     let (mut incoming_messages, client) =
-        TwitchIRCClient::<SecureTCPTransport, StaticLoginCredentials>::new(client_config);
+        TwitchIRCClient::<SecureTCPTransport, L>::new(client_config);
 
     let create_channels_db = pool.clone();
+    let stream_poller_db = pool.clone();
+    let channel_reconciler_db = pool.clone();
+    let coverage_db = pool.clone();
+    let sharding_db = pool.clone();
+
+    // every handle_message task holds a sender, so receiver returns None once all of them finished
+    let (in_flight_tx, mut in_flight_rx) = mpsc::channel::<()>(1);
+    let mut loop_shutdown = shutdown.clone();
 
-    let handle = spawn(async move {
+    let mut handle = spawn(async move {
         let pool = pool.clone();
-        while let Some(message) = incoming_messages.recv().await {
-            info!("recieved message {:?}", message);
+        loop {
+            let message = tokio::select! {
+                message = incoming_messages.recv() => message,
+                _ = loop_shutdown.changed() => break,
+            };
+
+            let Some(message) = message else {
+                break;
+            };
+
+            metrics::observe_irc_message(&message);
+            let channel = metrics::message_channel(&message).map(str::to_owned);
+
+            if logging::sample_message() {
+                log_with!(
+                    Debug,
+                    [channel = channel.as_deref().unwrap_or_default()],
+                    "received message {:?}",
+                    message
+                );
+            }
+            if let Some(channel) = &channel {
+                metrics::MESSAGES_RECEIVED
+                    .with_label_values(&[channel])
+                    .inc();
+            }
 
             let Ok(conn) = pool.get() else {
                 println!("Database error for message. Check logs for details");
                 error!("Database error while getting connection to database from pool");
+                if let Some(channel) = &channel {
+                    metrics::message_dropped(channel, "db_pool");
+                }
                 continue;
             };
 
+            let in_flight = in_flight_tx.clone();
             tokio::spawn(async move {
                 handle_message(message, conn).await;
+                drop(in_flight);
             });
         }
     });
 
-    let config = get_config_async!().await;
+    coverage::irc_client_started();
 
-    let token = AppAccessToken::get_app_access_token(
-        &helix_client,
-        ClientId::new(config.twitchapi.clientid.clone()),
-        ClientSecret::new(config.twitchapi.clientsecret.clone()),
-        Scope::all(),
-    )
-    .await
-    .into_report()
-    .change_context(RunError::GetTokenError)?;
+    // pollers and join retries die together with this irc client
+    let mut tasks = TaskGroup::default();
+    let joined_channels: JoinedChannels = Default::default();
 
     // TODO maybe get rid of channels in config?
     // or add another method of adding
     // will probably do when I do api crate and there add method of adding channel
-    for channel in get_config_async!().await.channels.iter() {
-        info!("Joining channel {}", &channel);
-        let channel_info = &helix_client
-            .get_channel_from_login(&channel[..], &token)
-            .await
-            .into_report()
-            .change_context(RunError::ApiError)?
-            .ok_or_else(|| Report::new(RunError::ChannelNotExists(channel.clone())))?;
+    let sharded = get_config_async!().await.sharding.is_some();
+
+    // with sharding, channels are joined once this instance gets their lease
+    if sharded {
+        tasks.spawn(sharding::run(
+            sharding_db,
+            helix_client.clone(),
+            token_manager.clone(),
+            client.clone(),
+            joined_channels.clone(),
+        ));
+    } else {
+        tasks.spawn(config_channels::run(
+            create_channels_db,
+            helix_client.clone(),
+            token_manager.clone(),
+            client.clone(),
+            joined_channels.clone(),
+        ));
+    }
 
-        let db_conn = &create_channels_db
-            .get()
-            .into_report()
-            .change_context(RunError::DbPoolError)?;
+    tasks.spawn(stream_poller::run(
+        stream_poller_db,
+        helix_client.clone(),
+        token_manager.clone(),
+        joined_channels.clone(),
+    ));
+
+    tasks.spawn(channel_reconciler::run(
+        channel_reconciler_db,
+        helix_client.clone(),
+        token_manager.clone(),
+        client.clone(),
+        joined_channels.clone(),
+    ));
+
+    tasks.spawn(coverage::run(coverage_db.clone()));
+
+    tokio::select! {
+        result = &mut handle => {
+            result
+                .into_report()
+                .change_context(RunError::HandleError)
+                .attach_printable("Handle returned an error")?;
+
+            return Err(Report::new(RunError::HandleError)
+                .attach_printable("Handle run joined to main thread"));
+        }
+        _ = shutdown.changed() => {}
+    }
 
-        create_channel_if_not_exists(
-            &db_conn,
-            channel_info.broadcaster_id.clone().into_string(),
-            channel.clone(),
-        )
-        .change_context(RunError::DatabaseError)?;
+    info!("Shutting down, no longer reading messages");
+
+    // channels were recorded right up to now, time until next start is collection gap
+    if metrics::irc_alive() {
+        coverage::heartbeat(&coverage_db);
+    }
 
-        client.join(channel.clone()).expect("lol");
+    for channel in joined_channels.read().await.iter() {
+        client.part(channel.channel_name.clone());
     }
 
+    // other instances can take over right away instead of waiting for leases to expire
+    if sharded {
+        sharding::release_all(&coverage_db).await;
+    }
+
+    // loop stops on its own as it watches shutdown too
     handle
         .await
         .into_report()
         .change_context(RunError::HandleError)
         .attach_printable("Handle returned an error")?;
 
-    Err(Report::new(RunError::HandleError).attach_printable("Handle run joined to main thread"))
+    let shutdown_timeout = get_config_async!().await.shutdown_timeout_secs;
+
+    info!("Waiting up to {shutdown_timeout}s for messages that are being saved");
+
+    timeout(Duration::from_secs(shutdown_timeout), in_flight_rx.recv())
+        .await
+        .into_report()
+        .change_context(RunError::ShutdownTimeout)?;
+
+    Ok(())
+}
+
+/// Resolves channel with helix, makes sure it's in db and joins it under its current login
+pub async fn join_channel<L: LoginCredentials>(
+    pool: &DbPool,
+    helix_client: &HelixClient<'static, reqwest::Client>,
+    token_manager: &TokenManager,
+    client: &TwitchIRCClient<SecureTCPTransport, L>,
+    channel: &ChannelConfig,
+) -> error_stack::Result<Channel, RunError> {
+    info!("Joining channel {}", channel.name);
+    let db_channel = resolve_channel(pool, helix_client, token_manager, channel).await?;
+
+    client
+        .join(db_channel.channel_name.clone())
+        .into_report()
+        .change_context_lazy(|| RunError::InvalidChannelName(db_channel.channel_name.clone()))?;
+
+    Ok(db_channel)
+}
+
+/// Resolves channel from config with helix and makes sure it's in db under its current login
+/// with policy from config
+pub async fn resolve_channel(
+    pool: &DbPool,
+    helix_client: &HelixClient<'static, reqwest::Client>,
+    token_manager: &TokenManager,
+    channel: &ChannelConfig,
+) -> error_stack::Result<Channel, RunError> {
+    let name = &channel.name;
+    let channel_info = token_manager
+        .call(|token| async move { helix_client.get_channel_from_login(&name[..], &token).await })
+        .await
+        .change_context(RunError::ApiError)?;
+
+    let channel_info = match channel_info {
+        Some(channel_info) => channel_info,
+        // channel could have been renamed since it was put in config
+        None => find_renamed_channel(pool, helix_client, token_manager, name)
+            .await?
+            .ok_or_else(|| Report::new(RunError::ChannelNotExists(name.clone())))?,
+    };
+
+    let login = channel_info.broadcaster_login.to_string();
+    if &login != name {
+        warn!("channel {name} is now called {login}. Update channels in config");
+    }
+
+    let db_conn = &pool
+        .get()
+        .into_report()
+        .change_context(RunError::DbPoolError)?;
+
+    let db_channel =
+        create_channel_if_not_exists(db_conn, channel_info.broadcaster_id.into_string(), login)
+            .change_context(RunError::DatabaseError)?;
+
+    set_policy(db_conn, db_channel, &channel.policy).change_context(RunError::DatabaseError)
+}
+
+/// Looks for channel known under `channel_name` in db and gets its current info by twitch id
+async fn find_renamed_channel(
+    pool: &DbPool,
+    helix_client: &HelixClient<'static, reqwest::Client>,
+    token_manager: &TokenManager,
+    channel_name: &str,
+) -> error_stack::Result<Option<ChannelInformation>, RunError> {
+    let channel = {
+        let db_conn = pool
+            .get()
+            .into_report()
+            .change_context(RunError::DbPoolError)?;
+
+        match get_channel_by_name(&db_conn, channel_name).change_context(RunError::DatabaseError)? {
+            Some(channel) => Some(channel),
+            None => get_channel_by_old_name(&db_conn, channel_name)
+                .change_context(RunError::DatabaseError)?,
+        }
+    };
+
+    let Some(channel) = channel else {
+        return Ok(None);
+    };
+
+    token_manager
+        .call(|token| {
+            let twitch_channel_id = channel.twitch_channel_id.clone();
+            async move {
+                helix_client
+                    .get_channel_from_id(twitch_channel_id, &token)
+                    .await
+            }
+        })
+        .await
+        .change_context(RunError::ApiError)
 }
 
 async fn handle_message(message: ServerMessage, db_conn: PooledConnection) {
+    let retry_limit = get_config_async!().await.database_op_retry_limit;
+
     match message {
         ServerMessage::Privmsg(msg) => {
-            handle_priv_msg(msg, db_conn);
+            handle_priv_msg(msg, db_conn, retry_limit);
         }
         ServerMessage::UserNotice(user_notice) => {
             // user notice can be subs, resubs, raids etc.
-            handle_user_notice(user_notice, db_conn);
+            handle_user_notice(user_notice, db_conn, retry_limit);
+        }
+        ServerMessage::RoomState(room_state) => {
+            handle_room_state(room_state, db_conn, retry_limit);
+        }
+        ServerMessage::Join(join) => {
+            coverage::handle_join(join, db_conn);
         }
         _ => {}
     }
 }
 
-fn handle_priv_msg(msg: PrivmsgMessage, db_conn: PooledConnection) {
+fn handle_priv_msg(msg: PrivmsgMessage, db_conn: PooledConnection, retry_limit: u8) {
     let channel = get_channel_by_twitch_id(&db_conn, &msg.channel_id);
 
     let channel = match channel {
         Ok(v) => v,
         Err(err) => {
-            log::error!("{err}");
+            log_with!(
+                Error,
+                [
+                    channel = msg.channel_login,
+                    error = logging::error_chain(&err)
+                ],
+                "{err}"
+            );
+            metrics::message_dropped(&msg.channel_login, "database");
             return;
         }
     };
 
     let Some(channel) = channel else {
-        log::error!("error getting channel");
+        log_with!(
+            Error,
+            [channel = msg.channel_login],
+            "error getting channel"
+        );
+        metrics::message_dropped(&msg.channel_login, "unknown_channel");
         return;
     };
 
     let msg_type = get_msg_type_from_privmsg(&msg);
+    if !channel.stores(msg_type.into()) {
+        return;
+    }
 
-    let message = create_message(
-        &db_conn,
-        msg.message_text,
-        msg_type,
-        channel.id,
-        msg.server_timestamp,
-        msg.bits.map(|bits| bits as i64),
-        None,
-        msg.sender.id,
-        msg.sender.login,
-        msg.sender.name,
-    );
-
-    if let Err(err) = message {
-        log::error!("couldn't save message!! err: {err}");
+    let channel_login = msg.channel_login;
+    let user_login = msg.sender.login.clone();
+
+    let text = channel.store_text.then_some(msg.message_text);
+
+    let insert_timer = metrics::INSERT_DURATION.start_timer();
+    let message = with_retries(retry_limit, || {
+        create_message(
+            &db_conn,
+            text.clone(),
+            msg_type,
+            channel.id,
+            msg.server_timestamp,
+            msg.bits.map(|bits| bits as i64),
+            None,
+            msg.sender.id.clone(),
+            msg.sender.login.clone(),
+            msg.sender.name.clone(),
+        )
+    });
+
+    insert_timer.observe_duration();
+
+    match message {
+        Ok(_) => metrics::message_stored(&channel_login),
+        Err(err) => {
+            log_with!(
+                Error,
+                [
+                    channel = channel_login,
+                    user = user_login,
+                    error = logging::error_chain(&err)
+                ],
+                "couldn't save message!! err: {err}"
+            );
+            metrics::message_dropped(&channel_login, "database");
+        }
     }
 }
 
-fn handle_user_notice(user_notice: UserNoticeMessage, db_conn: PooledConnection) {
+fn handle_user_notice(user_notice: UserNoticeMessage, db_conn: PooledConnection, retry_limit: u8) {
     match user_notice.event {
         UserNoticeEvent::SubOrResub {
             is_resub,
@@ -203,16 +532,33 @@ fn handle_user_notice(user_notice: UserNoticeMessage, db_conn: PooledConnection)
             let channel = match channel {
                 Ok(v) => v,
                 Err(err) => {
-                    log::error!("{err}");
+                    log_with!(
+                        Error,
+                        [
+                            channel = user_notice.channel_login,
+                            error = logging::error_chain(&err)
+                        ],
+                        "{err}"
+                    );
+                    metrics::message_dropped(&user_notice.channel_login, "database");
                     return;
                 }
             };
 
             let Some(channel) = channel else {
-                log::error!("error getting channel");
+                log_with!(
+                    Error,
+                    [channel = user_notice.channel_login],
+                    "error getting channel"
+                );
+                metrics::message_dropped(&user_notice.channel_login, "unknown_channel");
                 return;
             };
 
+            if !channel.stores(EventKind::Sub) {
+                return;
+            }
+
             let tier = match Tier::try_from(&sub_plan[..]) {
                 Ok(tier) => tier,
                 Err(err) => {
@@ -225,6 +571,7 @@ fn handle_user_notice(user_notice: UserNoticeMessage, db_conn: PooledConnection)
                         sub_plan,
                         err
                     );
+                    metrics::message_dropped(&user_notice.channel_login, "invalid_data");
                     return;
                 }
             };
@@ -234,27 +581,151 @@ fn handle_user_notice(user_notice: UserNoticeMessage, db_conn: PooledConnection)
                 tier: tier,
             };
 
-            let message = create_message(
-                &db_conn,
-                msg,
-                MsgType::Sub,
-                channel.id,
-                user_notice.server_timestamp,
-                None,
-                Some(new_resub),
-                user_notice.sender.id,
-                user_notice.sender.login,
-                user_notice.sender.name,
-            );
+            let channel_login = user_notice.channel_login;
+            let user_login = user_notice.sender.login.clone();
+
+            let text = channel.store_text.then_some(msg);
+
+            let insert_timer = metrics::INSERT_DURATION.start_timer();
+            let message = with_retries(retry_limit, || {
+                create_message(
+                    &db_conn,
+                    text.clone(),
+                    MsgType::Sub,
+                    channel.id,
+                    user_notice.server_timestamp,
+                    None,
+                    Some(new_resub.clone()),
+                    user_notice.sender.id.clone(),
+                    user_notice.sender.login.clone(),
+                    user_notice.sender.name.clone(),
+                )
+            });
+
+            insert_timer.observe_duration();
 
-            if let Err(err) = message {
-                log::error!("couldn't save message!! error: {err}");
+            match message {
+                Ok(_) => metrics::message_stored(&channel_login),
+                Err(err) => {
+                    log_with!(
+                        Error,
+                        [
+                            channel = channel_login,
+                            user = user_login,
+                            error = logging::error_chain(&err)
+                        ],
+                        "couldn't save message!! error: {err}"
+                    );
+                    metrics::message_dropped(&channel_login, "database");
+                }
             }
         }
         _ => {}
     }
 }
 
+fn handle_room_state(room_state: RoomStateMessage, db_conn: PooledConnection, retry_limit: u8) {
+    // ROOMSTATE doesn't carry tmi-sent-ts, so the time of receiving is used instead
+    let change_time = Utc::now();
+
+    let channel = get_channel_by_twitch_id(&db_conn, &room_state.channel_id);
+
+    let channel = match channel {
+        Ok(v) => v,
+        Err(err) => {
+            log_with!(
+                Error,
+                [
+                    channel = room_state.channel_login,
+                    error = logging::error_chain(&err)
+                ],
+                "{err}"
+            );
+            metrics::message_dropped(&room_state.channel_login, "database");
+            return;
+        }
+    };
+
+    let Some(channel) = channel else {
+        log_with!(
+            Error,
+            [channel = room_state.channel_login],
+            "error getting channel"
+        );
+        metrics::message_dropped(&room_state.channel_login, "unknown_channel");
+        return;
+    };
+
+    if !channel.stores(EventKind::RoomState) {
+        return;
+    }
+
+    let followers_only_minutes = room_state.follwers_only.map(|mode| match mode {
+        FollowersOnlyMode::Disabled => -1,
+        FollowersOnlyMode::Enabled(duration) => (duration.as_secs() / 60) as i32,
+    });
+
+    let new_room_state_change = NewRoomStateChange {
+        channel_id: channel.id,
+        change_time,
+        emote_only: room_state.emote_only,
+        followers_only_minutes,
+        r9k: room_state.r9k,
+        slow_mode_seconds: room_state
+            .slow_mode
+            .map(|duration| duration.as_secs() as i32),
+        subscribers_only: room_state.subscribers_only,
+    };
+
+    let room_state_change = with_retries(retry_limit, || {
+        room_state_changes::create(&db_conn, new_room_state_change.clone())
+    });
+
+    match room_state_change {
+        Ok(_) => metrics::message_stored(&room_state.channel_login),
+        Err(err) => {
+            log_with!(
+                Error,
+                [
+                    channel = room_state.channel_login,
+                    error = logging::error_chain(&err)
+                ],
+                "couldn't save room state change!! error: {err}"
+            );
+            metrics::message_dropped(&room_state.channel_login, "database");
+        }
+    }
+}
+
+/// Runs `op` again while it fails with error that goes away on its own, at most `retry_limit` times
+fn with_retries<T>(
+    retry_limit: u8,
+    mut op: impl FnMut() -> error_stack::Result<T, diesel::result::Error>,
+) -> error_stack::Result<T, diesel::result::Error> {
+    let mut attempt = 0;
+
+    loop {
+        match op() {
+            Err(err) if attempt < retry_limit && is_transient(err.current_context()) => {
+                attempt += 1;
+                warn!("database operation failed, retrying ({attempt}/{retry_limit}): {err}");
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Serialization failures and deadlocks can go away when statement is simply run again
+fn is_transient(err: &diesel::result::Error) -> bool {
+    use diesel::result::{DatabaseErrorKind, Error};
+
+    match err {
+        Error::DatabaseError(DatabaseErrorKind::SerializationFailure, _) => true,
+        Error::DatabaseError(_, info) => info.message().contains("deadlock detected"),
+        _ => false,
+    }
+}
+
 fn get_msg_type_from_privmsg(msg: &PrivmsgMessage) -> MsgType {
     if msg.is_action {
         return MsgType::Action;
@@ -266,3 +737,66 @@ fn get_msg_type_from_privmsg(msg: &PrivmsgMessage) -> MsgType {
 
     return MsgType::Message;
 }
+
+#[cfg(test)]
+mod tests {
+    use diesel::result::{DatabaseErrorKind, Error};
+    use error_stack::Report;
+
+    use super::*;
+
+    fn database_error(kind: DatabaseErrorKind, message: &str) -> Report<Error> {
+        Report::new(Error::DatabaseError(kind, Box::new(message.to_owned())))
+    }
+
+    #[test]
+    fn retries_transient_errors_up_to_limit() {
+        let mut attempts = 0;
+
+        let result: error_stack::Result<(), Error> = with_retries(3, || {
+            attempts += 1;
+            Err(database_error(
+                DatabaseErrorKind::SerializationFailure,
+                "could not serialize access",
+            ))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 4);
+    }
+
+    #[test]
+    fn returns_value_once_retry_succeeds() {
+        let mut attempts = 0;
+
+        let result = with_retries(3, || {
+            attempts += 1;
+            if attempts < 2 {
+                Err(database_error(
+                    DatabaseErrorKind::__Unknown,
+                    "deadlock detected",
+                ))
+            } else {
+                Ok(attempts)
+            }
+        });
+
+        assert_eq!(result.unwrap(), 2);
+    }
+
+    #[test]
+    fn doesnt_retry_other_errors() {
+        let mut attempts = 0;
+
+        let result: error_stack::Result<(), Error> = with_retries(3, || {
+            attempts += 1;
+            Err(database_error(
+                DatabaseErrorKind::ForeignKeyViolation,
+                "violates foreign key constraint",
+            ))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+}